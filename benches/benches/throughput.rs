@@ -0,0 +1,71 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use wirehair_wrapper::wirehair::{wirehair_init, BlockId, WirehairDecoder, WirehairEncoder};
+
+const BLOCK_SIZE: u32 = 1024;
+// The smallest message is two blocks, since the library needs N >= 2
+const MESSAGE_SIZES: [(usize, &str); 3] = [
+    (2 * 1024, "2KB"),
+    (1024 * 1024, "1MB"),
+    (16 * 1024 * 1024, "16MB"),
+];
+
+fn message(size: usize) -> Vec<u8> {
+    (0..size).map(|i| (i * 7 + i / 251) as u8).collect()
+}
+
+// Repair blocks are the expensive ones, the original blocks are plain copies of the message
+fn encode(c: &mut Criterion) {
+    assert!(wirehair_init().is_ok());
+
+    let mut group = c.benchmark_group("encode");
+    for &(size, label) in MESSAGE_SIZES.iter() {
+        let encoder = WirehairEncoder::from_slice(&message(size), BLOCK_SIZE).unwrap();
+        let block_count = u64::from(encoder.block_count());
+        let mut block = vec![0u8; BLOCK_SIZE as usize];
+
+        group.throughput(Throughput::Elements(block_count));
+        group.bench_function(BenchmarkId::new("repair_blocks", label), |b| {
+            b.iter(|| {
+                for block_id in block_count..block_count * 2 {
+                    encoder.encode(block_id, &mut block).unwrap();
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+// A full decode from every block but each tenth, so the decoder has to solve for the lost ones
+fn decode(c: &mut Criterion) {
+    let init = wirehair_init().unwrap();
+
+    let mut group = c.benchmark_group("decode");
+    for &(size, label) in MESSAGE_SIZES.iter() {
+        let encoder = WirehairEncoder::from_slice(&message(size), BLOCK_SIZE).unwrap();
+        let block_count = encoder.block_count();
+        let blocks: Vec<(BlockId, Vec<u8>)> = encoder
+            .encode_all(block_count + block_count / 5 + 10)
+            .unwrap()
+            .into_iter()
+            .filter(|(block_id, _)| block_id.0 % 10 != 0)
+            .collect();
+
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_function(BenchmarkId::new("recover", label), |b| {
+            b.iter_batched(
+                || WirehairDecoder::new(&init, size as u64, BLOCK_SIZE).unwrap(),
+                |decoder| {
+                    decoder
+                        .decode_many(blocks.iter().map(|(block_id, block)| (*block_id, &block[..])))
+                        .unwrap();
+                    decoder.recover_vec().unwrap()
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, encode, decode);
+criterion_main!(benches);
@@ -1,14 +1,148 @@
 extern crate cc;
 
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+const SOURCES: [&str; 4] = [
+    "src/wirehair/wirehair.cpp",
+    "src/wirehair/gf256.cpp",
+    "src/wirehair/WirehairCodec.cpp",
+    "src/wirehair/WirehairTools.cpp",
+];
+const INCLUDE_DIR: &str = "src/wirehair";
+
 fn main() {
-    cc::Build::new()
-        .cpp(true)
-        .file("src/wirehair/wirehair.cpp")
-        .file("src/wirehair/gf256.cpp")
-        .file("src/wirehair/WirehairCodec.cpp")
-        .file("src/wirehair/WirehairTools.cpp")
-        .include("src/wirehair")
-        .flag("-msse4.1")
-        .shared_flag(true)
-        .compile("wirehair");
+    if env::var_os("CARGO_FEATURE_SYSTEM_WIREHAIR").is_some() {
+        link_system();
+        return;
+    }
+
+    let target_arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap();
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap();
+    let avx2 = env::var_os("CARGO_FEATURE_AVX2").is_some();
+    let neon = env::var_os("CARGO_FEATURE_NEON").is_some();
+    // `static` is the default, `shared` wins when both are enabled
+    let shared = env::var_os("CARGO_FEATURE_SHARED").is_some();
+
+    // Without these cargo doesn't notice edits of the vendored sources (headers included)
+    for source in SOURCES.iter() {
+        println!("cargo:rerun-if-changed={}", source);
+    }
+    println!("cargo:rerun-if-changed={}", INCLUDE_DIR);
+
+    let mut build = cc::Build::new();
+    build.cpp(true).files(SOURCES.iter()).include(INCLUDE_DIR);
+
+    let is_msvc = build.get_compiler().is_like_msvc();
+
+    // The sources are C++11. MSVC has no switch below C++14, which is its default anyway.
+    if !is_msvc {
+        build.std("c++11");
+    }
+    // cc follows the opt-level of the profile, but the codec's throughput hinges on -O3,
+    // so every profile without debug info gets it. CXXFLAGS go after these flags and win.
+    if env::var("DEBUG").as_deref() != Ok("true") {
+        build.opt_level(3);
+    }
+
+    match target_arch.as_str() {
+        "x86" | "x86_64" if is_msvc => {
+            // cl.exe rejects GCC-style -m flags. It compiles SSSE3 intrinsics without
+            // any switch, x64 always has SSE2 and 32-bit x86 needs it spelled out.
+            // The C++ runtime is linked by the MSVC toolchain itself.
+            if avx2 {
+                build.flag("/arch:AVX2");
+            } else if target_arch == "x86" {
+                build.flag("/arch:SSE2");
+            }
+        }
+        "x86" | "x86_64" => {
+            build.flag(if avx2 { "-mavx2" } else { "-msse4.1" });
+        }
+        _ => {
+            // gf256 only avoids the x86 intrinsics when one of its "mobile" platforms is defined.
+            // LINUX_ARM probes NEON through /proc/self/auxv, IOS is the variant without OS probing.
+            match target_os.as_str() {
+                "android" => build.define("ANDROID", None),
+                "linux" => build.define("LINUX_ARM", None),
+                _ => build.define("IOS", None),
+            };
+
+            // NEON is always there on aarch64, 32-bit ARM gets it only on request
+            if target_arch == "aarch64" || (target_arch == "arm" && neon) {
+                build.define("HAVE_ARM_NEON_H", None);
+
+                if target_arch == "arm" && !is_msvc {
+                    build.flag("-mfpu=neon");
+                }
+            }
+        }
+    }
+
+    if shared {
+        link_shared(&build, &target_os, is_msvc);
+    } else {
+        build.compile("wirehair");
+    }
+}
+
+// Nothing is compiled, the linker gets pointed at an installed library.
+// WIREHAIR_LIB_DIR wins over pkg-config so a library without a .pc file can still be used.
+#[cfg(feature = "system-wirehair")]
+fn link_system() {
+    println!("cargo:rerun-if-env-changed=WIREHAIR_LIB_DIR");
+
+    if let Some(lib_dir) = env::var_os("WIREHAIR_LIB_DIR") {
+        println!("cargo:rustc-link-search=native={}", PathBuf::from(lib_dir).display());
+        println!("cargo:rustc-link-lib=wirehair");
+        return;
+    }
+
+    if let Err(e) = pkg_config::Config::new().probe("wirehair") {
+        panic!("libwirehair not found, set WIREHAIR_LIB_DIR or install its pkg-config file: {}", e);
+    }
+}
+
+#[cfg(not(feature = "system-wirehair"))]
+fn link_system() {
+    unreachable!("the system-wirehair feature is off")
+}
+
+// cc only produces static archives, so the objects are linked into a shared library by hand
+fn link_shared(build: &cc::Build, target_os: &str, is_msvc: bool) {
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+    // WIREHAIR_EXPORT is dllexport only for DLL builds of the library itself
+    let objects = build
+        .clone()
+        .define("WIREHAIR_BUILDING", None)
+        .define("WIREHAIR_DLL", None)
+        .pic(true)
+        .compile_intermediates();
+
+    let mut command: Command = build.get_compiler().to_command();
+    if is_msvc {
+        command
+            .arg("/LD")
+            .args(&objects)
+            .arg(format!("/Fe{}", out_dir.join("wirehair.dll").display()));
+    } else {
+        let file_name = match target_os {
+            "macos" | "ios" => "libwirehair.dylib",
+            _ => "libwirehair.so",
+        };
+        command
+            .arg("-shared")
+            .args(&objects)
+            .arg("-o")
+            .arg(out_dir.join(file_name));
+    }
+
+    let status = command.status().expect("failed to run the linker");
+    assert!(status.success(), "failed to link the wirehair shared library");
+
+    // Cargo puts search paths inside the target directory on the library path of tests and `cargo run`
+    println!("cargo:rustc-link-search=native={}", out_dir.display());
+    println!("cargo:rustc-link-lib=dylib=wirehair");
 }
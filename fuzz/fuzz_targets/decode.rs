@@ -0,0 +1,53 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use wirehair_wrapper::wirehair::{wirehair_init, WirehairDecoder, WirehairResult};
+
+const MESSAGE_SIZE: u64 = 1000;
+const BLOCK_SIZE: u32 = 64;
+
+// The input is a sequence of operations, each one is
+//   op: u8, block id: u32 LE, declared length: u8, block length: u8, block bytes
+// Set the top bit of `op` to move the id past u32::MAX.
+fuzz_target!(|data: &[u8]| {
+    let init = wirehair_init().unwrap();
+    let mut decoder = WirehairDecoder::new(&init, MESSAGE_SIZE, BLOCK_SIZE).unwrap();
+    let mut solved = false;
+
+    let mut data = data;
+    while data.len() >= 7 {
+        let op = data[0];
+        let mut block_id = u64::from(u32::from_le_bytes([data[1], data[2], data[3], data[4]]));
+        if op & 0x80 != 0 {
+            block_id += 1 << 32;
+        }
+        let declared_len = u32::from(data[5]);
+        let block_len = (data[6] as usize).min(data.len() - 7);
+        let block = &data[7..7 + block_len];
+        data = &data[7 + block_len..];
+
+        match op & 0x7f {
+            0 => solved |= decoder.decode(block_id, block, declared_len) == Ok(WirehairResult::Success),
+            1 => solved |= decoder.decode_dedup(block_id, block) == Ok(true),
+            2 => {
+                let mut message = vec![0u8; declared_len as usize * 8];
+                let _ = decoder.recover(&mut message);
+            }
+            3 => {
+                let original = decoder.recovered_block(block_id as u32).unwrap_or_default();
+                assert!(original.len() <= BLOCK_SIZE as usize);
+            }
+            4 => decoder.retain_original_blocks(),
+            5 => {
+                let _ = decoder.reset();
+                solved = false;
+            }
+            _ => {}
+        }
+    }
+
+    match decoder.recover_vec() {
+        Ok(message) => assert_eq!(message.len() as u64, MESSAGE_SIZE),
+        Err(_) => assert!(!solved),
+    }
+});
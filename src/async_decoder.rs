@@ -0,0 +1,117 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::channel::oneshot;
+use futures::Sink;
+
+use crate::wirehair::{
+    wirehair_init, Packet, Result, WirehairDecoder, WirehairError, WirehairResult,
+};
+
+/// Adapts `WirehairDecoder` to a `Sink` of packets, e.g. for forwarding a stream of
+/// packets received from a socket. The codec itself is synchronous: every packet is
+/// decoded right inside `start_send`. Packets arriving after the message is solved are ignored.
+pub struct AsyncDecoder {
+    decoder: WirehairDecoder,
+    complete: bool,
+    on_complete: Option<oneshot::Sender<()>>,
+}
+
+impl AsyncDecoder {
+    /// Returns the decoder and a future which resolves once enough packets were sent into it
+    pub fn new(
+        message_size: u64,
+        block_size: u32,
+    ) -> Result<(AsyncDecoder, oneshot::Receiver<()>)> {
+        let (sender, receiver) = oneshot::channel();
+        let decoder = AsyncDecoder {
+            decoder: WirehairDecoder::new(&wirehair_init()?, message_size, block_size)?,
+            complete: false,
+            on_complete: Some(sender),
+        };
+
+        Ok((decoder, receiver))
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Recovers the message. Fails with `NeedMore` if it is not solved yet.
+    pub fn into_message(self) -> Result<Vec<u8>> {
+        self.decoder.recover_vec()
+    }
+}
+
+impl Sink<Packet> for AsyncDecoder {
+    type Error = WirehairError;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, packet: Packet) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        if this.complete {
+            return Ok(());
+        }
+
+        if this.decoder.decode_packet(&packet)? == WirehairResult::Success {
+            this.complete = true;
+
+            if let Some(on_complete) = this.on_complete.take() {
+                // nobody may be waiting for the notification anymore
+                let _ = on_complete.send(());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wirehair::WirehairEncoder;
+    use futures::executor::block_on;
+    use futures::{stream, StreamExt};
+    use rand::seq::SliceRandom;
+
+    #[test]
+    fn shuffled_packets_are_decoded() {
+        assert!(wirehair_init().is_ok());
+
+        let message: Vec<u8> = (0..500).map(|i| (i * 11) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+
+        let mut packets: Vec<Packet> = (0..30)
+            .map(|block_id| encoder.encode_packet(block_id).unwrap())
+            .collect();
+        packets.shuffle(&mut rand::thread_rng());
+
+        let (mut decoder, complete) = AsyncDecoder::new(500, 50).unwrap();
+
+        block_on(async {
+            stream::iter(packets.into_iter().map(Ok))
+                .forward(&mut decoder)
+                .await
+                .unwrap();
+
+            complete.await.unwrap();
+        });
+
+        assert!(decoder.is_complete());
+        assert_eq!(decoder.into_message().unwrap(), message);
+
+        let (unsolved, _) = AsyncDecoder::new(500, 50).unwrap();
+        assert_eq!(unsolved.into_message(), Err(WirehairError::NeedMore));
+    }
+}
@@ -0,0 +1,158 @@
+use std::convert::TryFrom;
+
+use crate::wirehair::{
+    BlockId, Result, WirehairDecoder, WirehairEncoder, WirehairError, WirehairResult,
+};
+
+/// Bytes appended to every block: the valid length and a CRC32 of the block id (as u64), the block
+/// and that length, both as little-endian u32. The id isn't sent, but a block arriving under
+/// another id than it was encoded for fails the check.
+pub const TRAILER_SIZE: usize = 8;
+
+/// Wraps an encoder so every block carries a trailer which `ChecksummedDecoder` verifies.
+/// Costs `TRAILER_SIZE` extra bytes per block.
+pub struct ChecksummedEncoder {
+    encoder: WirehairEncoder,
+}
+
+impl ChecksummedEncoder {
+    pub fn new(encoder: WirehairEncoder) -> ChecksummedEncoder {
+        ChecksummedEncoder { encoder }
+    }
+
+    pub fn encoder(&self) -> &WirehairEncoder {
+        &self.encoder
+    }
+
+    /// Returns block `block_id` truncated to its valid length, followed by the trailer
+    pub fn encode(&self, block_id: impl Into<BlockId>) -> Result<Vec<u8>> {
+        let block_id = block_id.into();
+        let mut frame = vec![0u8; self.encoder.block_size() as usize + TRAILER_SIZE];
+        let len = self.encoder.encode(block_id, &mut frame)?;
+        frame.truncate(len as usize);
+
+        frame.extend_from_slice(&len.to_le_bytes());
+        let checksum = checksum(block_id, &frame);
+        frame.extend_from_slice(&checksum.to_le_bytes());
+
+        Ok(frame)
+    }
+}
+
+/// Verifies the trailer added by `ChecksummedEncoder` and feeds only intact blocks to the decoder
+pub struct ChecksummedDecoder {
+    decoder: WirehairDecoder,
+}
+
+impl ChecksummedDecoder {
+    pub fn new(decoder: WirehairDecoder) -> ChecksummedDecoder {
+        ChecksummedDecoder { decoder }
+    }
+
+    pub fn decoder(&self) -> &WirehairDecoder {
+        &self.decoder
+    }
+
+    pub fn into_decoder(self) -> WirehairDecoder {
+        self.decoder
+    }
+
+    /// Decodes a block produced by `ChecksummedEncoder::encode`.
+    /// A block which doesn't match its trailer is rejected with `ChecksumMismatch`.
+    pub fn decode(
+        &self,
+        block_id: impl Into<BlockId>,
+        frame: &[u8],
+    ) -> Result<WirehairResult> {
+        let block_id = block_id.into();
+        let block = verify(block_id, frame)?;
+
+        self.decoder.decode(block_id, block, block.len() as u32)
+    }
+}
+
+// Returns the block part of `frame` if the trailer matches it and `block_id`
+fn verify(block_id: BlockId, frame: &[u8]) -> Result<&[u8]> {
+    if frame.len() < TRAILER_SIZE {
+        return Err(WirehairError::ChecksumMismatch);
+    }
+
+    let (checked, checksum) = frame.split_at(frame.len() - 4);
+    let (block, len) = checked.split_at(checked.len() - 4);
+
+    let checksum = u32::from_le_bytes(<[u8; 4]>::try_from(checksum).unwrap());
+    let len = u32::from_le_bytes(<[u8; 4]>::try_from(len).unwrap());
+
+    if self::checksum(block_id, checked) != checksum || len as usize != block.len() {
+        return Err(WirehairError::ChecksumMismatch);
+    }
+
+    Ok(block)
+}
+
+// CRC32 of the block id followed by the block and its length
+fn checksum(block_id: BlockId, checked: &[u8]) -> u32 {
+    !crc32_update(crc32_update(!0, &block_id.0.to_le_bytes()), checked)
+}
+
+// CRC-32 as used by zlib and Ethernet (reflected polynomial 0xEDB88320), without the
+// initial and final inversion so it can be fed piecewise
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & 0u32.wrapping_sub(crc & 1));
+        }
+    }
+
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wirehair::wirehair_init;
+
+    #[test]
+    fn crc32_matches_reference() {
+        assert_eq!(!crc32_update(!0, b""), 0);
+        assert_eq!(!crc32_update(!0, b"123456789"), 0xCBF4_3926);
+        assert_eq!(!crc32_update(crc32_update(!0, b"1234"), b"56789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn corrupted_blocks_are_rejected() {
+        let init = wirehair_init().unwrap();
+
+        let message: Vec<u8> = (0..520).map(|i| (i * 37) as u8).collect();
+        let encoder = ChecksummedEncoder::new(WirehairEncoder::from_slice(&message, 50).unwrap());
+        let decoder = ChecksummedDecoder::new(WirehairDecoder::new(&init, 520, 50).unwrap());
+
+        let mut result = Ok(WirehairResult::NeedMore);
+        for block_id in 0..30u64 {
+            let mut frame = encoder.encode(block_id).unwrap();
+            assert!(frame.len() <= 50 + TRAILER_SIZE);
+
+            if block_id == 3 {
+                frame[17] ^= 0x04;
+                assert_eq!(
+                    decoder.decode(block_id, &frame),
+                    Err(WirehairError::ChecksumMismatch)
+                );
+                continue;
+            }
+
+            result = decoder.decode(block_id, &frame);
+            if result == Ok(WirehairResult::Success) {
+                break;
+            }
+        }
+        assert_eq!(result, Ok(WirehairResult::Success));
+
+        assert_eq!(decoder.decode(0, &[1, 2, 3]), Err(WirehairError::ChecksumMismatch));
+        // The id of an intact block is checked too, so one off by a flipped bit is rejected
+        let frame = encoder.encode(40).unwrap();
+        assert_eq!(decoder.decode(40 ^ 0x100, &frame), Err(WirehairError::ChecksumMismatch));
+        assert_eq!(decoder.into_decoder().recover_vec(), Ok(message));
+    }
+}
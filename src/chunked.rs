@@ -0,0 +1,311 @@
+use std::convert::TryFrom;
+use std::ops::Range;
+
+use crate::wirehair::{
+    max_block_count, BlockId, FecParams, Result, WirehairDecoder, WirehairEncoder, WirehairError,
+    MIN_BLOCK_COUNT,
+};
+
+/// Bytes in front of every frame: the chunk index and the block id, both as little-endian u32.
+/// The library takes 32-bit block ids, so nothing is lost.
+pub const CHUNK_HEADER_SIZE: usize = 8;
+
+/// Encodes a message too large for a single codec as independent chunks of at most
+/// `chunk_blocks` blocks, each with its own encoder. A block is addressed by its chunk index
+/// and its block id within that chunk. The blocks are spread evenly over the chunks, so none of
+/// them ends up below the minimum N; a message too small for that is `InvalidInput`.
+#[derive(Debug, Clone)]
+pub struct ChunkedEncoder {
+    layout: Layout,
+    encoders: Vec<WirehairEncoder>,
+}
+
+impl ChunkedEncoder {
+    /// Chunks of at most `max_block_count()` blocks
+    pub fn new(message: &[u8], block_size_bytes: u32) -> Result<ChunkedEncoder> {
+        ChunkedEncoder::with_chunk_blocks(message, block_size_bytes, max_block_count())
+    }
+
+    pub fn with_chunk_blocks(
+        message: &[u8],
+        block_size_bytes: u32,
+        chunk_blocks: u32,
+    ) -> Result<ChunkedEncoder> {
+        let layout = Layout::new(message.len() as u64, block_size_bytes, chunk_blocks)?;
+        let encoders = (0..layout.chunk_count)
+            .map(|chunk_index| {
+                let chunk = layout.chunk_range(chunk_index);
+                WirehairEncoder::from_slice(
+                    &message[chunk.start as usize..chunk.end as usize],
+                    block_size_bytes,
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(ChunkedEncoder { layout, encoders })
+    }
+
+    pub fn message_size(&self) -> u64 {
+        self.layout.message_size
+    }
+
+    pub fn block_size(&self) -> u32 {
+        self.layout.block_size
+    }
+
+    pub fn chunk_count(&self) -> u32 {
+        self.encoders.len() as u32
+    }
+
+    pub fn chunk(&self, chunk_index: u32) -> Option<&WirehairEncoder> {
+        self.encoders.get(chunk_index as usize)
+    }
+
+    /// Writes block `block_id` of chunk `chunk_index` into `block` and returns its valid bytes.
+    /// An unknown chunk is `InvalidInput`.
+    pub fn encode(
+        &self,
+        chunk_index: u32,
+        block_id: impl Into<BlockId>,
+        block: &mut [u8],
+    ) -> Result<usize> {
+        self.chunk(chunk_index)
+            .ok_or(WirehairError::InvalidInput)?
+            .encode_into(block_id, block)
+    }
+
+    /// Returns the header followed by the valid bytes of the block, ready to be sent.
+    /// Block ids beyond u32 are `InvalidInput`.
+    pub fn encode_frame(&self, chunk_index: u32, block_id: impl Into<BlockId>) -> Result<Vec<u8>> {
+        let block_id = u32::try_from(block_id.into().0).map_err(|_| WirehairError::InvalidInput)?;
+
+        let mut frame = vec![0u8; CHUNK_HEADER_SIZE + self.layout.block_size as usize];
+        frame[..4].copy_from_slice(&chunk_index.to_le_bytes());
+        frame[4..CHUNK_HEADER_SIZE].copy_from_slice(&block_id.to_le_bytes());
+        let len = self.encode(chunk_index, u64::from(block_id), &mut frame[CHUNK_HEADER_SIZE..])?;
+        frame.truncate(CHUNK_HEADER_SIZE + len);
+
+        Ok(frame)
+    }
+}
+
+/// Receives the blocks of a `ChunkedEncoder` with the same sizes, decoding every chunk on its own
+/// and concatenating them once all are solved
+#[derive(Debug)]
+pub struct ChunkedDecoder {
+    layout: Layout,
+    decoders: Vec<WirehairDecoder>,
+    // Whether each chunk's decoder has solved its part
+    solved: Vec<bool>,
+}
+
+impl ChunkedDecoder {
+    pub fn new(message_size_bytes: u64, block_size_bytes: u32) -> Result<ChunkedDecoder> {
+        ChunkedDecoder::with_chunk_blocks(message_size_bytes, block_size_bytes, max_block_count())
+    }
+
+    /// `chunk_blocks` has to be the one the encoder was created with
+    pub fn with_chunk_blocks(
+        message_size_bytes: u64,
+        block_size_bytes: u32,
+        chunk_blocks: u32,
+    ) -> Result<ChunkedDecoder> {
+        let layout = Layout::new(message_size_bytes, block_size_bytes, chunk_blocks)?;
+        let decoders = (0..layout.chunk_count)
+            .map(|chunk_index| {
+                let chunk = layout.chunk_range(chunk_index);
+                let chunk_size = chunk.end - chunk.start;
+
+                WirehairDecoder::with_params(FecParams::new(chunk_size, block_size_bytes))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(ChunkedDecoder {
+            layout,
+            solved: vec![false; decoders.len()],
+            decoders,
+        })
+    }
+
+    pub fn message_size(&self) -> u64 {
+        self.layout.message_size
+    }
+
+    pub fn chunk_count(&self) -> u32 {
+        self.decoders.len() as u32
+    }
+
+    pub fn chunks_remaining(&self) -> u32 {
+        self.solved.iter().filter(|&&solved| !solved).count() as u32
+    }
+
+    pub fn chunk(&self, chunk_index: u32) -> Option<&WirehairDecoder> {
+        self.decoders.get(chunk_index as usize)
+    }
+
+    /// Decodes a whole block of chunk `chunk_index` and returns whether the whole message
+    /// can be recovered now. An unknown chunk is `InvalidInput`.
+    pub fn decode(
+        &mut self,
+        chunk_index: u32,
+        block_id: impl Into<BlockId>,
+        block: &[u8],
+    ) -> Result<bool> {
+        let index = chunk_index as usize;
+        let decoder = self.decoders.get(index).ok_or(WirehairError::InvalidInput)?;
+
+        self.solved[index] = decoder.feed(block_id, block)?;
+
+        Ok(self.chunks_remaining() == 0)
+    }
+
+    /// Decodes a frame produced by `ChunkedEncoder::encode_frame`.
+    /// A frame without data after the header is `InvalidInput`.
+    pub fn decode_frame(&mut self, frame: &[u8]) -> Result<bool> {
+        if frame.len() <= CHUNK_HEADER_SIZE {
+            return Err(WirehairError::InvalidInput);
+        }
+
+        let chunk_index = u32::from_le_bytes(<[u8; 4]>::try_from(&frame[..4]).unwrap());
+        let block_id = u32::from_le_bytes(<[u8; 4]>::try_from(&frame[4..8]).unwrap());
+
+        self.decode(chunk_index, u64::from(block_id), &frame[CHUNK_HEADER_SIZE..])
+    }
+
+    /// The whole message, `NeedMore` until every chunk is solved
+    pub fn recover_vec(&self) -> Result<Vec<u8>> {
+        if self.chunks_remaining() > 0 {
+            return Err(WirehairError::NeedMore);
+        }
+
+        let mut message = vec![0u8; self.layout.message_size as usize];
+        for (chunk_index, decoder) in (0..).zip(&self.decoders) {
+            let chunk = self.layout.chunk_range(chunk_index);
+            decoder.recover(&mut message[chunk.start as usize..chunk.end as usize])?;
+        }
+
+        Ok(message)
+    }
+}
+
+// How a message is cut into chunks, worked out the same way on both ends
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    message_size: u64,
+    block_size: u32,
+    chunk_count: u64,
+    // Blocks in the smaller chunks, the first `larger_chunks` chunks carry one more
+    chunk_blocks: u64,
+    larger_chunks: u64,
+}
+
+impl Layout {
+    fn new(message_size: u64, block_size: u32, chunk_blocks: u32) -> Result<Layout> {
+        if message_size == 0 || block_size == 0 || chunk_blocks == 0 {
+            return Err(WirehairError::InvalidInput);
+        }
+
+        // As few chunks as `chunk_blocks` allows, with the blocks spread evenly over them,
+        // so no chunk is left with a single block
+        let block_count = message_size.div_ceil(u64::from(block_size));
+        let chunk_count = block_count.div_ceil(u64::from(chunk_blocks));
+        if block_count / chunk_count < u64::from(MIN_BLOCK_COUNT) {
+            return Err(WirehairError::InvalidInput);
+        }
+
+        Ok(Layout {
+            message_size,
+            block_size,
+            chunk_count,
+            chunk_blocks: block_count / chunk_count,
+            larger_chunks: block_count % chunk_count,
+        })
+    }
+
+    // Bytes of the message in chunk `chunk_index`, only the last chunk may end in a partial block
+    fn chunk_range(&self, chunk_index: u64) -> Range<u64> {
+        let first_block = chunk_index * self.chunk_blocks + chunk_index.min(self.larger_chunks);
+        let blocks = self.chunk_blocks + u64::from(chunk_index < self.larger_chunks);
+        let block_size = u64::from(self.block_size);
+
+        first_block * block_size..self.message_size.min((first_block + blocks) * block_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_are_spread_evenly() {
+        let layout = Layout::new(1001, 10, 50).unwrap();
+        // 101 blocks need 3 chunks of at most 50 blocks, 34, 34 and 33 of them
+        assert_eq!(layout.chunk_count, 3);
+        assert_eq!(layout.chunk_range(0), 0..340);
+        assert_eq!(layout.chunk_range(1), 340..680);
+        assert_eq!(layout.chunk_range(2), 680..1001);
+
+        assert_eq!(Layout::new(500, 10, 50).unwrap().chunk_count, 1);
+        assert!(Layout::new(0, 10, 50).is_err());
+        // 3 blocks can't be cut into chunks of at most 2 without a chunk of 1
+        assert!(Layout::new(30, 10, 2).is_err());
+    }
+
+    #[test]
+    fn no_chunk_is_left_with_a_single_block() {
+        let message: Vec<u8> = (0..61).map(|i| (i * 7) as u8).collect();
+        // 7 blocks in chunks of at most 3, split 3, 2 and 2 rather than 3, 3 and 1
+        let encoder = ChunkedEncoder::with_chunk_blocks(&message, 10, 3).unwrap();
+        let mut decoder = ChunkedDecoder::with_chunk_blocks(61, 10, 3).unwrap();
+        assert_eq!(encoder.chunk_count(), 3);
+        for chunk_index in 0..encoder.chunk_count() {
+            assert!(encoder.chunk(chunk_index).unwrap().block_count() >= MIN_BLOCK_COUNT);
+        }
+
+        let mut solved = false;
+        'send: for block_id in 0..100u64 {
+            for chunk_index in 0..encoder.chunk_count() {
+                let frame = encoder.encode_frame(chunk_index, block_id).unwrap();
+                solved = decoder.decode_frame(&frame).unwrap();
+                if solved {
+                    break 'send;
+                }
+            }
+        }
+        assert!(solved);
+        assert_eq!(decoder.recover_vec(), Ok(message));
+    }
+
+    #[test]
+    fn messages_beyond_a_single_codec_are_recovered() {
+        let block_size = 2;
+        let message_size = u64::from(max_block_count()) * u64::from(block_size) + 1000;
+        let message: Vec<u8> = (0..message_size).map(|i| (i * 31 + i / 256) as u8).collect();
+        assert!(WirehairEncoder::from_slice(&message, block_size).is_err());
+
+        let encoder = ChunkedEncoder::new(&message, block_size).unwrap();
+        let mut decoder = ChunkedDecoder::new(message_size, block_size).unwrap();
+        assert_eq!(encoder.chunk_count(), 2);
+        assert_eq!(decoder.chunk_count(), 2);
+        assert_eq!(decoder.recover_vec(), Err(WirehairError::NeedMore));
+
+        let empty = [0u8; CHUNK_HEADER_SIZE];
+        assert_eq!(decoder.decode_frame(&empty), Err(WirehairError::InvalidInput));
+        assert_eq!(decoder.decode(2, 0, &[0, 0]), Err(WirehairError::InvalidInput));
+
+        // The chunks take turns and every fifth block is lost
+        let mut solved = false;
+        'send: for block_id in (0..100_000u64).filter(|block_id| block_id % 5 != 4) {
+            for chunk_index in 0..encoder.chunk_count() {
+                let frame = encoder.encode_frame(chunk_index, block_id).unwrap();
+                solved = decoder.decode_frame(&frame).unwrap();
+                if solved {
+                    break 'send;
+                }
+            }
+        }
+        assert!(solved);
+        assert_eq!(decoder.chunks_remaining(), 0);
+        assert_eq!(decoder.recover_vec(), Ok(message));
+    }
+}
@@ -0,0 +1,250 @@
+use crate::wirehair::{BlockId, Result, WirehairDecoder, WirehairEncoder, WirehairError};
+
+/// The encode/decode/recover surface of a codec, so protocol code can be written against
+/// `dyn FecCodec` and tested with `MockCodec` instead of the native library.
+pub trait FecCodec {
+    fn message_size(&self) -> u64;
+
+    fn block_size(&self) -> u32;
+
+    /// Writes block `block_id` into `block` and returns its valid bytes.
+    /// Only codecs which hold the whole message can encode, the others return `InvalidInput`.
+    fn encode(&self, block_id: BlockId, block: &mut [u8]) -> Result<usize>;
+
+    /// Takes a received block and returns whether the message can be recovered now.
+    /// Encoders return `InvalidInput`.
+    fn decode(&mut self, block_id: BlockId, block: &[u8]) -> Result<bool>;
+
+    /// The whole message, `NeedMore` while it can't be recovered yet
+    fn recover(&self) -> Result<Vec<u8>>;
+}
+
+impl FecCodec for WirehairEncoder {
+    fn message_size(&self) -> u64 {
+        WirehairEncoder::message_size(self)
+    }
+
+    fn block_size(&self) -> u32 {
+        WirehairEncoder::block_size(self)
+    }
+
+    fn encode(&self, block_id: BlockId, block: &mut [u8]) -> Result<usize> {
+        self.encode_into(block_id, block)
+    }
+
+    fn decode(&mut self, _: BlockId, _: &[u8]) -> Result<bool> {
+        Err(WirehairError::InvalidInput)
+    }
+
+    fn recover(&self) -> Result<Vec<u8>> {
+        Ok(self.message().to_vec())
+    }
+}
+
+impl FecCodec for WirehairDecoder {
+    fn message_size(&self) -> u64 {
+        WirehairDecoder::message_size(self)
+    }
+
+    fn block_size(&self) -> u32 {
+        WirehairDecoder::block_size(self)
+    }
+
+    fn encode(&self, _: BlockId, _: &mut [u8]) -> Result<usize> {
+        Err(WirehairError::InvalidInput)
+    }
+
+    fn decode(&mut self, block_id: BlockId, block: &[u8]) -> Result<bool> {
+        self.feed(block_id, block)
+    }
+
+    fn recover(&self) -> Result<Vec<u8>> {
+        self.recover_vec()
+    }
+}
+
+/// In-memory stand-in for a codec, written in plain Rust. Block ids below N are the message
+/// blocks as they are, every id from N on is the same parity block, the XOR of all of them.
+/// So unlike wirehair it only makes up for a single lost block, which is plenty for testing
+/// protocol logic. Any N >= 1 is accepted.
+#[derive(Debug, Clone)]
+pub struct MockCodec {
+    message_size: u64,
+    block_size: u32,
+    // Message blocks by id, each padded to `block_size`
+    originals: Vec<Option<Vec<u8>>>,
+    parity: Option<Vec<u8>>,
+}
+
+impl MockCodec {
+    /// A mock encoder holding `message`, it can encode right away
+    pub fn encoder(message: &[u8], block_size: u32) -> Result<MockCodec> {
+        let mut codec = MockCodec::decoder(message.len() as u64, block_size)?;
+        for (original, chunk) in codec.originals.iter_mut().zip(message.chunks(block_size as usize)) {
+            let mut block = chunk.to_vec();
+            block.resize(block_size as usize, 0);
+            *original = Some(block);
+        }
+
+        Ok(codec)
+    }
+
+    /// A mock decoder which has received nothing yet
+    pub fn decoder(message_size: u64, block_size: u32) -> Result<MockCodec> {
+        if message_size == 0 || block_size == 0 {
+            return Err(WirehairError::InvalidInput);
+        }
+
+        let block_count = message_size.div_ceil(u64::from(block_size)) as usize;
+
+        Ok(MockCodec {
+            message_size,
+            block_size,
+            originals: vec![None; block_count],
+            parity: None,
+        })
+    }
+
+    fn is_complete(&self) -> bool {
+        self.originals.iter().all(Option::is_some)
+    }
+
+    fn xor<'a>(&self, blocks: impl Iterator<Item = &'a Vec<u8>>) -> Vec<u8> {
+        let mut xor = vec![0u8; self.block_size as usize];
+        for block in blocks {
+            xor.iter_mut().zip(block).for_each(|(x, byte)| *x ^= byte);
+        }
+
+        xor
+    }
+
+    // Valid bytes of message block `index`, only the last one can be short
+    fn original_len(&self, index: usize) -> usize {
+        let start = index as u64 * u64::from(self.block_size);
+
+        (self.message_size - start).min(u64::from(self.block_size)) as usize
+    }
+}
+
+impl FecCodec for MockCodec {
+    fn message_size(&self) -> u64 {
+        self.message_size
+    }
+
+    fn block_size(&self) -> u32 {
+        self.block_size
+    }
+
+    fn encode(&self, block_id: BlockId, block: &mut [u8]) -> Result<usize> {
+        if block.len() < self.block_size as usize || !self.is_complete() {
+            return Err(WirehairError::InvalidInput);
+        }
+
+        let index = block_id.0 as usize;
+        if block_id.0 < self.originals.len() as u64 {
+            let len = self.original_len(index);
+            block[..len].copy_from_slice(&self.originals[index].as_ref().unwrap()[..len]);
+
+            return Ok(len);
+        }
+
+        let len = self.block_size as usize;
+        block[..len].copy_from_slice(&self.xor(self.originals.iter().flatten()));
+
+        Ok(len)
+    }
+
+    fn decode(&mut self, block_id: BlockId, block: &[u8]) -> Result<bool> {
+        if block.len() > self.block_size as usize {
+            return Err(WirehairError::InvalidInput);
+        }
+        if self.is_complete() {
+            return Ok(true);
+        }
+
+        let mut padded = block.to_vec();
+        padded.resize(self.block_size as usize, 0);
+        match self.originals.get_mut(block_id.0 as usize) {
+            Some(original) => *original = Some(padded),
+            None => self.parity = Some(padded),
+        }
+
+        let missing: Vec<usize> = (0..self.originals.len())
+            .filter(|&index| self.originals[index].is_none())
+            .collect();
+        // With a single block missing, XOR-ing the parity block with the rest leaves that block
+        if let (&[index], Some(parity)) = (&missing[..], &self.parity) {
+            let mut recovered = self.xor(self.originals.iter().flatten().chain(Some(parity)));
+            // The padding of a short last block is zero, not whatever the XOR left there
+            recovered[self.original_len(index)..].iter_mut().for_each(|byte| *byte = 0);
+            self.originals[index] = Some(recovered);
+        }
+
+        Ok(self.is_complete())
+    }
+
+    fn recover(&self) -> Result<Vec<u8>> {
+        if !self.is_complete() {
+            return Err(WirehairError::NeedMore);
+        }
+
+        let mut message: Vec<u8> = self.originals.iter().flatten().flatten().copied().collect();
+        message.truncate(self.message_size as usize);
+
+        Ok(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wirehair::wirehair_init;
+
+    // What an application would do: send blocks until the receiver has the message
+    fn transfer(encoder: &dyn FecCodec, decoder: &mut dyn FecCodec, lost: u64) -> Result<Vec<u8>> {
+        let mut block = vec![0u8; encoder.block_size() as usize];
+        for block_id in (0..100).filter(|&block_id| block_id != lost) {
+            let len = encoder.encode(BlockId(block_id), &mut block)?;
+            if decoder.decode(BlockId(block_id), &block[..len])? {
+                return decoder.recover();
+            }
+        }
+
+        Err(WirehairError::ExtraInsufficient)
+    }
+
+    #[test]
+    fn mock_codec_works_as_trait_object() {
+        let message: Vec<u8> = (0..230).map(|i| (i * 7 + 1) as u8).collect();
+
+        let encoder: Box<dyn FecCodec> = Box::new(MockCodec::encoder(&message, 50).unwrap());
+        let mut decoder: Box<dyn FecCodec> = Box::new(MockCodec::decoder(230, 50).unwrap());
+        assert_eq!(decoder.recover(), Err(WirehairError::NeedMore));
+
+        // The short last block is lost and rebuilt from the parity block
+        assert_eq!(transfer(&*encoder, &mut *decoder, 4), Ok(message.clone()));
+
+        let mut decoder = MockCodec::decoder(230, 50).unwrap();
+        assert_eq!(transfer(&*encoder, &mut decoder, 1), Ok(message.clone()));
+        assert_eq!(decoder.encode(BlockId(7), &mut [0u8; 50]), Ok(50));
+
+        // N = 1 works as well
+        let encoder = MockCodec::encoder(&message[..20], 50).unwrap();
+        let mut decoder = MockCodec::decoder(20, 50).unwrap();
+        assert_eq!(transfer(&encoder, &mut decoder, 0), Ok(message[..20].to_vec()));
+    }
+
+    #[test]
+    fn native_codecs_work_as_trait_objects() {
+        let init = wirehair_init().unwrap();
+
+        let message: Vec<u8> = (0..520).map(|i| (i * 11 + 5) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+        let decoder = WirehairDecoder::new(&init, 520, 50).unwrap();
+        let encoder: Box<dyn FecCodec> = Box::new(encoder);
+        let mut decoder: Box<dyn FecCodec> = Box::new(decoder);
+
+        assert_eq!(transfer(&*encoder, &mut *decoder, 3), Ok(message.clone()));
+        assert_eq!(encoder.recover(), Ok(message));
+    }
+}
@@ -0,0 +1,131 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::wirehair::{
+    wirehair_init, BlockId, OwnedEncoder, Result, WirehairDecoder, WirehairEncoder, WirehairError,
+    WirehairResult,
+};
+
+/// Reads the whole file at `path` and builds an encoder over its contents.
+/// The last block of the file is shorter than `block_size` unless the file size is a multiple of it.
+pub fn encode_file(path: &Path, block_size: u32) -> Result<FileEncoder> {
+    let contents = fs::read(path).map_err(|e| WirehairError::Io(e.kind()))?;
+    let encoder = OwnedEncoder::new(contents, block_size)?;
+
+    Ok(FileEncoder { encoder })
+}
+
+pub struct FileEncoder {
+    encoder: OwnedEncoder,
+}
+
+impl FileEncoder {
+    pub fn encoder(&self) -> &WirehairEncoder {
+        &self.encoder
+    }
+
+    pub fn contents(&self) -> &[u8] {
+        self.encoder.message()
+    }
+}
+
+/// Collects blocks of a file encoded by `encode_file` and writes the file to `path` once it is recovered
+pub struct FileDecoder {
+    decoder: WirehairDecoder,
+    path: PathBuf,
+}
+
+impl FileDecoder {
+    pub fn new(path: &Path, file_size: u64, block_size: u32) -> Result<FileDecoder> {
+        Ok(FileDecoder {
+            decoder: WirehairDecoder::new(&wirehair_init()?, file_size, block_size)?,
+            path: path.to_path_buf(),
+        })
+    }
+
+    pub fn decode(
+        &self,
+        block_id: impl Into<BlockId>,
+        block: &[u8],
+    ) -> Result<WirehairResult> {
+        self.decoder.decode(block_id, block, block.len() as u32)
+    }
+
+    /// Recovers the file and writes it to the path given on creation.
+    /// Fails with `NeedMore` without creating the file until `decode` has returned `Success`.
+    pub fn finish(self) -> Result<()> {
+        let contents = self.decoder.recover_vec()?;
+
+        fs::write(&self.path, contents).map_err(|e| WirehairError::Io(e.kind()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wirehair::wirehair_init;
+    use std::env;
+    use std::io;
+
+    #[test]
+    fn file_round_trip_works() {
+        assert!(wirehair_init().is_ok());
+
+        let dir = env::temp_dir();
+        let source = dir.join(format!("wirehair-source-{}", std::process::id()));
+        let target = dir.join(format!("wirehair-target-{}", std::process::id()));
+
+        // 10 007 bytes do not split evenly into 100 byte blocks
+        let contents: Vec<u8> = (0..10_007u32).map(|i| (i * 7 + i / 256) as u8).collect();
+        fs::write(&source, &contents).unwrap();
+
+        let file_encoder = encode_file(&source, 100).unwrap();
+        let file_decoder = FileDecoder::new(&target, contents.len() as u64, 100).unwrap();
+
+        for block in file_encoder.encoder().blocks(0) {
+            let (block_id, block) = block.unwrap();
+
+            // drop every fifth block
+            if block_id.0 % 5 == 0 {
+                continue;
+            }
+
+            if file_decoder.decode(block_id, &block).unwrap() == WirehairResult::Success {
+                break;
+            }
+        }
+        file_decoder.finish().unwrap();
+
+        assert_eq!(fs::read(&target).unwrap(), contents);
+
+        fs::remove_file(&source).unwrap();
+        fs::remove_file(&target).unwrap();
+    }
+
+    #[test]
+    fn early_finish_writes_nothing() {
+        assert!(wirehair_init().is_ok());
+
+        let target = env::temp_dir().join(format!("wirehair-early-{}", std::process::id()));
+        let contents: Vec<u8> = (0..1000u32).map(|i| (i * 3) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&contents, 100).unwrap();
+        let file_decoder = FileDecoder::new(&target, 1000, 100).unwrap();
+
+        for (block_id, block) in encoder.encode_all(5).unwrap() {
+            assert_eq!(file_decoder.decode(block_id, &block), Ok(WirehairResult::NeedMore));
+        }
+
+        assert_eq!(file_decoder.finish(), Err(WirehairError::NeedMore));
+        assert!(!target.exists());
+    }
+
+    #[test]
+    fn missing_file_is_reported() {
+        let path = env::temp_dir().join("wirehair-this-file-does-not-exist");
+
+        assert_eq!(
+            encode_file(&path, 100).err(),
+            Some(WirehairError::Io(io::ErrorKind::NotFound))
+        );
+    }
+}
@@ -0,0 +1,100 @@
+// Scalar arithmetic in GF(256), using the tables of the codec itself (polynomial 0x14D).
+// Every function initializes the library first and panics if that fails.
+
+use crate::wirehair::wirehair_init;
+
+extern "C" {
+    fn wirehair_gf256_mul(x: u8, y: u8) -> u8;
+    fn wirehair_gf256_div(x: u8, y: u8) -> u8;
+    fn wirehair_gf256_inv(x: u8) -> u8;
+}
+
+// The tables are filled in by wirehair_init, before that every lookup returns 0
+fn init() {
+    if let Err(e) = wirehair_init() {
+        panic!("GF(256) tables are unavailable, wirehair_init failed: {}", e);
+    }
+}
+
+/// a + b, which is also a - b
+pub fn add(a: u8, b: u8) -> u8 {
+    a ^ b
+}
+
+/// a * b
+pub fn mul(a: u8, b: u8) -> u8 {
+    init();
+
+    unsafe { wirehair_gf256_mul(a, b) }
+}
+
+/// a / b. Panics if `b` is 0.
+pub fn div(a: u8, b: u8) -> u8 {
+    assert_ne!(b, 0, "division by zero in GF(256)");
+    init();
+
+    unsafe { wirehair_gf256_div(a, b) }
+}
+
+/// 1 / a. Panics if `a` is 0.
+pub fn inv(a: u8) -> u8 {
+    assert_ne!(a, 0, "zero has no inverse in GF(256)");
+    init();
+
+    unsafe { wirehair_gf256_inv(a) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Shift-and-add multiplication reduced by the codec's polynomial
+    fn reference_mul(a: u8, b: u8) -> u8 {
+        let (mut a, mut b) = (u16::from(a), b);
+        let mut product = 0u16;
+        while b != 0 {
+            if b & 1 != 0 {
+                product ^= a;
+            }
+            b >>= 1;
+            a <<= 1;
+            if a & 0x100 != 0 {
+                a ^= 0x14D;
+            }
+        }
+
+        product as u8
+    }
+
+    #[test]
+    fn products_match_the_reference() {
+        for a in 0..=255u8 {
+            for b in 0..=255u8 {
+                assert_eq!(mul(a, b), reference_mul(a, b), "{} * {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn field_axioms_hold() {
+        for a in 1..=255u8 {
+            assert_eq!(mul(a, inv(a)), 1);
+            assert_eq!(inv(inv(a)), a);
+            assert_eq!(add(a, a), 0);
+
+            for b in (1..=255u8).step_by(7) {
+                assert_eq!(div(mul(a, b), b), a);
+                assert_eq!(div(a, b), mul(a, inv(b)));
+                assert_eq!(mul(a, add(b, 3)), add(mul(a, b), mul(a, 3)));
+            }
+        }
+        assert_eq!(mul(0, 17), 0);
+        assert_eq!(div(0, 17), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "division by zero")]
+    fn division_by_zero_panics() {
+        div(1, 0);
+    }
+}
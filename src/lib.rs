@@ -1,44 +1,69 @@
+#[cfg(feature = "futures")]
+pub mod async_decoder;
+pub mod checksum;
+pub mod chunked;
+pub mod codec;
+pub mod file;
+#[cfg(not(feature = "system-wirehair"))]
+pub mod gf256;
+pub mod padded;
+pub mod systematic;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+
 pub mod wirehair {
-    use std::fmt::{Display, Error, Formatter};
-    use std::os::raw::{c_int, c_void};
-    use std::ptr::null;
-
-    #[repr(C)]
-    enum WirehairResultCode {
-        // Success code
-        Success = 0,
-        // More data is needed to decode.  This is normal and does not indicate a failure
-        NeedMore = 1,
-        // Other values are failure codes:
-        // A function parameter was invalid
-        InvalidInput = 2,
-        // Encoder needs a better dense seed
-        BadDenseSeed = 3,
-        // Encoder needs a better peel seed
-        BadPeelSeed = 4,
-        // N = ceil(messageBytes / blockBytes) is too small.
-        // Try reducing block_size or use a larger message
-        BadInputSmallN = 5,
-        // N = ceil(messageBytes / blockBytes) is too large.
-        // Try increasing block_size or use a smaller message
-        BadInputLargeN = 6,
-        // Not enough extra rows to solve it, must give up
-        ExtraInsufficient = 7,
-        // An error occurred during the request
-        Error = 8,
-        // Out of memory
-        OOM = 9,
-        // Platform is not supported yet
-        UnsupportedPlatform = 10,
-        Count,
-        /* for asserts */
-        Padding = 0x7fff_ffff,
-        /* int32_t padding */
-    }
-
-    #[link(name = "wirehair")]
+    use std::cell::{Cell, RefCell};
+    use std::collections::{HashMap, HashSet};
+    use std::convert::TryFrom;
+    use std::fmt::{Debug, Display, Error, Formatter};
+    use std::io::{self, Read, Write};
+    use std::mem::ManuallyDrop;
+    use std::ops::{Deref, DerefMut, Range};
+    use std::os::raw::{c_int, c_uint, c_void};
+    use std::ptr::{null, null_mut, NonNull};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex, OnceLock};
+
+    // Result codes of the library (WirehairResult_t in wirehair.h). The FFI functions return
+    // a plain c_int, because an out-of-range value must not be decoded into a Rust enum.
+    // Success code
+    const SUCCESS: c_int = 0;
+    // More data is needed to decode.  This is normal and does not indicate a failure
+    const NEED_MORE: c_int = 1;
+    // Other values are failure codes:
+    // A function parameter was invalid
+    const INVALID_INPUT: c_int = 2;
+    // Encoder needs a better dense seed
+    const BAD_DENSE_SEED: c_int = 3;
+    // Encoder needs a better peel seed
+    const BAD_PEEL_SEED: c_int = 4;
+    // N = ceil(messageBytes / blockBytes) is too small.
+    // Try reducing block_size or use a larger message
+    const BAD_INPUT_SMALL_N: c_int = 5;
+    // N = ceil(messageBytes / blockBytes) is too large.
+    // Try increasing block_size or use a smaller message
+    const BAD_INPUT_LARGE_N: c_int = 6;
+    // Not enough extra rows to solve it, must give up
+    const EXTRA_INSUFFICIENT: c_int = 7;
+    // An error occurred during the request
+    const ERROR: c_int = 8;
+    // Out of memory
+    const OOM: c_int = 9;
+    // Platform is not supported yet
+    const UNSUPPORTED_PLATFORM: c_int = 10;
+
+    // An installed library may be static or shared, so its kind is left to build.rs and the linker
+    #[cfg_attr(feature = "system-wirehair", link(name = "wirehair"))]
+    #[cfg_attr(
+        all(feature = "shared", not(feature = "system-wirehair")),
+        link(name = "wirehair", kind = "dylib")
+    )]
+    #[cfg_attr(
+        not(any(feature = "shared", feature = "system-wirehair")),
+        link(name = "wirehair", kind = "static")
+    )]
     extern "C" {
-        fn wirehair_init_(version: c_int) -> WirehairResultCode;
+        fn wirehair_init_(version: c_int) -> c_int;
         fn wirehair_encoder_create(
             reuse_codec_opt: *const c_void,
             message: *const u8,
@@ -47,11 +72,11 @@ pub mod wirehair {
         ) -> *const c_void;
         fn wirehair_encode(
             codec: *const c_void,
-            block_id: u64,
+            block_id: c_uint,
             block: *mut u8,
             block_size: u32,
             block_out_bytes: &mut u32,
-        ) -> WirehairResultCode;
+        ) -> c_int;
         fn wirehair_decoder_create(
             reuse_codec_opt: *const c_void,
             message_size_bytes: u64,
@@ -59,20 +84,37 @@ pub mod wirehair {
         ) -> *const c_void;
         fn wirehair_decode(
             codec: *const c_void,
-            block_id: u64,
+            block_id: c_uint,
             block: *const u8,
             block_out_bytes: u32,
-        ) -> WirehairResultCode;
+        ) -> c_int;
         fn wirehair_recover(
             codec: *const c_void,
             message: *mut u8,
             message_size_bytes: u64,
-        ) -> WirehairResultCode;
-        fn wirehair_decoder_becomes_encoder(codec: *const c_void) -> WirehairResultCode;
+        ) -> c_int;
+        fn wirehair_recover_block(
+            codec: *const c_void,
+            block_id: c_uint,
+            block: *mut u8,
+            block_out_bytes: &mut u32,
+        ) -> c_int;
+        fn wirehair_decoder_becomes_encoder(codec: *const c_void) -> c_int;
         fn wirehair_free(codec: *const c_void) -> c_void;
+        // Not part of upstream wirehair, only the vendored copy has it
+        #[cfg(not(feature = "system-wirehair"))]
+        fn wirehair_simd_level() -> c_int;
+        #[cfg(not(feature = "system-wirehair"))]
+        fn wirehair_max_block_count() -> u32;
     }
 
-    #[derive(Debug, PartialEq)]
+    /// The result of every fallible function of the crate. The error type is a parameter only so
+    /// that `Result<(), fmt::Error>` and the like keep working where this alias is in scope.
+    pub type Result<T, E = WirehairError> = std::result::Result<T, E>;
+
+    // A plain value without heap data, so errors are cheap to return and to store
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[allow(clippy::upper_case_acronyms)]
     pub enum WirehairError {
         InvalidInput,
         BadDenseSeed,
@@ -83,6 +125,16 @@ pub mod wirehair {
         Error,
         OOM,
         UnsupportedPlatform,
+        // Reading or writing a file failed, see `file`
+        Io(io::ErrorKind),
+        // The library was built for another version than `WIREHAIR_VERSION`
+        VersionMismatch,
+        // The decoder hasn't received enough blocks to recover the message yet
+        NeedMore,
+        // A block doesn't match its checksum, see `checksum::ChecksummedDecoder`
+        ChecksumMismatch,
+        // The library returned a code this wrapper doesn't know
+        Unknown(i32),
     }
 
     impl Display for WirehairError {
@@ -106,199 +158,3803 @@ pub mod wirehair {
                 WirehairError::Error => write!(f, "Unexpected error"),
                 WirehairError::OOM => write!(f, "Out of memory"),
                 WirehairError::UnsupportedPlatform => write!(f, "Platform is not supported yet"),
+                WirehairError::Io(kind) => write!(f, "Failed to read or write a file: {}", kind),
+                WirehairError::VersionMismatch => write!(
+                    f,
+                    "The library version does not match the one this wrapper was written for"
+                ),
+                WirehairError::NeedMore => write!(f, "More blocks are needed to recover the message"),
+                WirehairError::ChecksumMismatch => {
+                    write!(f, "The block is corrupted, its checksum does not match")
+                }
+                WirehairError::Unknown(code) => write!(f, "Unknown result code {}", code),
             }
         }
     }
 
-    #[derive(Debug, PartialEq)]
+    impl From<WirehairError> for io::Error {
+        fn from(error: WirehairError) -> io::Error {
+            let kind = match error {
+                WirehairError::OOM => io::ErrorKind::OutOfMemory,
+                WirehairError::InvalidInput => io::ErrorKind::InvalidInput,
+                WirehairError::ChecksumMismatch => io::ErrorKind::InvalidData,
+                WirehairError::Io(kind) => kind,
+                _ => io::ErrorKind::Other,
+            };
+
+            io::Error::new(kind, error.to_string())
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub enum WirehairResult {
         Success,
         NeedMore,
-        Internal,
     }
 
-    fn parse_wirehair_result(result: WirehairResultCode) -> Result<WirehairResult, WirehairError> {
-        match result {
-            WirehairResultCode::InvalidInput => Err(WirehairError::InvalidInput),
-            WirehairResultCode::BadDenseSeed => Err(WirehairError::BadDenseSeed),
-            WirehairResultCode::BadPeelSeed => Err(WirehairError::BadPeelSeed),
-            WirehairResultCode::BadInputSmallN => Err(WirehairError::BadInputSmallN),
-            WirehairResultCode::BadInputLargeN => Err(WirehairError::BadInputLargeN),
-            WirehairResultCode::ExtraInsufficient => Err(WirehairError::ExtraInsufficient),
-            WirehairResultCode::Error => Err(WirehairError::Error),
-            WirehairResultCode::OOM => Err(WirehairError::OOM),
-            WirehairResultCode::UnsupportedPlatform => Err(WirehairError::UnsupportedPlatform),
-            WirehairResultCode::Success => Ok(WirehairResult::Success),
-            WirehairResultCode::NeedMore => Ok(WirehairResult::NeedMore),
-            _ => Ok(WirehairResult::Internal),
-        }
-    }
-
-    pub fn wirehair_init() -> Result<(), WirehairError> {
-        let result = unsafe { parse_wirehair_result(wirehair_init_(2)) };
+    impl WirehairResult {
+        /// `Success`, the message can be recovered
+        pub fn is_complete(&self) -> bool {
+            *self == WirehairResult::Success
+        }
+
+        pub fn is_need_more(&self) -> bool {
+            *self == WirehairResult::NeedMore
+        }
+
+        /// `Some(())` on `Success`, `None` on `NeedMore`
+        pub fn into_option(self) -> Option<()> {
+            match self {
+                WirehairResult::Success => Some(()),
+                WirehairResult::NeedMore => None,
+            }
+        }
+    }
+
+    pub(crate) fn parse_wirehair_result(result: c_int) -> Result<WirehairResult> {
         match result {
-            Ok(_r) => Ok(()),
+            SUCCESS => Ok(WirehairResult::Success),
+            NEED_MORE => Ok(WirehairResult::NeedMore),
+            INVALID_INPUT => Err(WirehairError::InvalidInput),
+            BAD_DENSE_SEED => Err(WirehairError::BadDenseSeed),
+            BAD_PEEL_SEED => Err(WirehairError::BadPeelSeed),
+            BAD_INPUT_SMALL_N => Err(WirehairError::BadInputSmallN),
+            BAD_INPUT_LARGE_N => Err(WirehairError::BadInputLargeN),
+            EXTRA_INSUFFICIENT => Err(WirehairError::ExtraInsufficient),
+            ERROR => Err(WirehairError::Error),
+            OOM => Err(WirehairError::OOM),
+            UNSUPPORTED_PLATFORM => Err(WirehairError::UnsupportedPlatform),
+            code => Err(WirehairError::Unknown(code)),
+        }
+    }
+
+    // N = ceil(message_size / block_size) must stay within these bounds.
+    // MAX_BLOCK_COUNT is for const contexts, it is checked against the library in the tests.
+    pub const MIN_BLOCK_COUNT: u32 = 2;
+    pub const MAX_BLOCK_COUNT: u32 = 64000;
+
+    /// The largest N the linked library accepts (CAT_WIREHAIR_MAX_N)
+    #[cfg(not(feature = "system-wirehair"))]
+    pub fn max_block_count() -> u32 {
+        unsafe { wirehair_max_block_count() }
+    }
+
+    /// The largest N the linked library accepts. An installed upstream library doesn't export
+    /// it, so this is MAX_BLOCK_COUNT, which hasn't changed upstream in years.
+    #[cfg(feature = "system-wirehair")]
+    pub fn max_block_count() -> u32 {
+        MAX_BLOCK_COUNT
+    }
+
+    /// The largest message `block_size_bytes` blocks can carry, MAX_BLOCK_COUNT whole blocks
+    pub const fn max_message_size(block_size_bytes: u32) -> u64 {
+        block_size_bytes as u64 * MAX_BLOCK_COUNT as u64
+    }
+
+    /// The smallest message which still splits into MIN_BLOCK_COUNT blocks of `block_size_bytes`
+    pub const fn min_message_size(block_size_bytes: u32) -> u64 {
+        block_size_bytes as u64 * (MIN_BLOCK_COUNT as u64 - 1) + 1
+    }
+
+    // The library is the most efficient with N around 1000
+    const OPTIMAL_BLOCK_COUNT: u64 = 1000;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct FecParams {
+        pub message_size: u64,
+        pub block_size: u32,
+    }
+
+    impl FecParams {
+        pub fn new(message_size: u64, block_size: u32) -> FecParams {
+            FecParams {
+                message_size,
+                block_size,
+            }
+        }
+
+        /// Returns N = ceil(message_size / block_size) or the error the library would report for it
+        pub fn validate(&self) -> Result<u32> {
+            if self.message_size == 0 || self.block_size == 0 {
+                return Err(WirehairError::InvalidInput);
+            }
+
+            let block_size = u64::from(self.block_size);
+            let block_count = self.message_size.div_ceil(block_size);
+
+            if block_count < u64::from(MIN_BLOCK_COUNT) {
+                return Err(WirehairError::BadInputSmallN);
+            }
+            if block_count > u64::from(max_block_count()) {
+                return Err(WirehairError::BadInputLargeN);
+            }
+
+            Ok(block_count as u32)
+        }
+
+        /// Picks a block size which keeps N close to the most efficient value.
+        /// Messages that are too small or too large for any block size still fail `validate`.
+        pub fn suggest_block_size(message_size: u64) -> u32 {
+            let block_size = message_size.div_ceil(OPTIMAL_BLOCK_COUNT);
+
+            u32::try_from(block_size.max(1)).unwrap_or(u32::MAX)
+        }
+
+        /// The largest block size which fits into `mtu` bytes and keeps N valid.
+        /// Fails with `BadInputLargeN` if even `mtu` sized blocks make N too large.
+        pub fn block_size_for_mtu(message_size: u64, mtu: u32) -> Result<u32> {
+            if message_size == 0 || mtu == 0 {
+                return Err(WirehairError::InvalidInput);
+            }
+            if message_size < u64::from(MIN_BLOCK_COUNT) {
+                return Err(WirehairError::BadInputSmallN);
+            }
+
+            // N >= 2 as long as a block is smaller than the message
+            let block_size = u64::from(mtu).min(message_size - 1);
+            if block_size < message_size.div_ceil(u64::from(max_block_count())) {
+                return Err(WirehairError::BadInputLargeN);
+            }
+
+            Ok(block_size as u32)
+        }
+    }
+
+    /// Version of the library API (WIREHAIR_VERSION in wirehair.h) this wrapper was written for
+    pub const WIREHAIR_VERSION: i32 = 2;
+
+    pub fn wirehair_version() -> i32 {
+        WIREHAIR_VERSION
+    }
+
+    // wirehair_init_ reports a version mismatch as InvalidInput, since it has no parameters
+    // other than the version this is the only way InvalidInput can come out of it
+    pub(crate) fn parse_init_result(result: c_int) -> Result<()> {
+        match parse_wirehair_result(result) {
+            Ok(_) => Ok(()),
+            Err(WirehairError::InvalidInput) => Err(WirehairError::VersionMismatch),
             Err(e) => Err(e),
         }
     }
 
+    static INIT_RESULT: OnceLock<Result<()>> = OnceLock::new();
+
+    /// Proof that `wirehair_init` has succeeded, required by `WirehairEncoder::new` and
+    /// `WirehairDecoder::new`. Only `wirehair_init` hands it out.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Initialized {
+        _private: (),
+    }
+
+    // The native init fills global tables without any locking, so it runs exactly once
+    // and every later (or concurrent) call gets the cached result of that first run
+    pub fn wirehair_init() -> Result<Initialized> {
+        INIT_RESULT
+            .get_or_init(|| unsafe { parse_init_result(wirehair_init_(WIREHAIR_VERSION)) })
+            .map(|_| Initialized { _private: () })
+    }
+
+    /// Whether the library can run on this CPU at all. Initializes the library, only
+    /// `UnsupportedPlatform` counts as unsupported, other init errors are left to `wirehair_init`.
+    pub fn is_supported() -> bool {
+        wirehair_init() != Err(WirehairError::UnsupportedPlatform)
+    }
+
+    /// Vector instruction set the GF(256) math runs on.
+    /// Levels of the same architecture are ordered from slowest to fastest.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub enum SimdLevel {
+        Scalar,
+        Sse2,
+        Ssse3,
+        Avx2,
+        Neon,
+    }
+
+    /// Returns the SIMD path picked at runtime. The CPU is only probed by `wirehair_init`,
+    /// so this initializes the library first.
+    /// Not available with `system-wirehair`, an installed library doesn't export the probe result.
+    #[cfg(not(feature = "system-wirehair"))]
+    pub fn active_simd() -> SimdLevel {
+        let _ = wirehair_init();
+
+        match unsafe { wirehair_simd_level() } {
+            1 => SimdLevel::Sse2,
+            2 => SimdLevel::Ssse3,
+            3 => SimdLevel::Avx2,
+            4 => SimdLevel::Neon,
+            _ => SimdLevel::Scalar,
+        }
+    }
+
+    #[deprecated(since = "0.1.4", note = "use `WirehairEncoder::try_from(decoder)`")]
     pub fn wirehair_decoder_to_encoder(
         decoder: WirehairDecoder,
-    ) -> Result<WirehairEncoder, WirehairError> {
-        let result = unsafe { wirehair_decoder_becomes_encoder(decoder.native_handler) };
+    ) -> Result<WirehairEncoder> {
+        WirehairEncoder::try_from(decoder)
+    }
 
-        match parse_wirehair_result(result) {
-            Ok(_) => Ok(WirehairEncoder {
-                native_handler: decoder.native_handler,
-            }),
-            Err(e) => Err(e),
+    /// A codec in either role. A decoder can turn into an encoder, never the other way round.
+    #[derive(Debug)]
+    pub enum Codec {
+        Encoder(WirehairEncoder),
+        Decoder(WirehairDecoder),
+    }
+
+    impl Codec {
+        /// Turns a solved decoder into an encoder of the same message, consuming the decoder role.
+        /// An encoder is returned unchanged.
+        pub fn into_encoder(self) -> Result<Codec> {
+            match self {
+                Codec::Encoder(encoder) => Ok(Codec::Encoder(encoder)),
+                Codec::Decoder(decoder) => WirehairEncoder::try_from(decoder).map(Codec::Encoder),
+            }
+        }
+    }
+
+    /// Identifier of an encoded block. Ids below N are the original message blocks, the rest are repair blocks.
+    /// Ordered like the plain ids, so blocks kept in a `BTreeMap` come out in id order.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct BlockId(pub u64);
+
+    impl From<u64> for BlockId {
+        fn from(id: u64) -> BlockId {
+            BlockId(id)
+        }
+    }
+
+    impl From<BlockId> for u64 {
+        fn from(id: BlockId) -> u64 {
+            id.0
+        }
+    }
+
+    /// A block together with everything a receiver needs to decode it
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct Packet {
+        pub block_id: u64,
+        pub block_size: u32,
+        pub message_size: u64,
+        pub data: Vec<u8>,
+    }
+
+    /// Running totals of a codec, cheap enough to keep on every encode and decode.
+    /// Encoders only count `encoded`, decoders the rest. `reset` of a decoder keeps them.
+    #[derive(Debug, Default)]
+    pub struct Stats {
+        encoded: AtomicU64,
+        decoded: AtomicU64,
+        duplicates: AtomicU64,
+        recoveries: AtomicU64,
+    }
+
+    /// The counters of `Stats` at one point in time
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct StatsSnapshot {
+        /// Blocks written by `encode`
+        pub encoded: u64,
+        /// Blocks accepted by `decode`
+        pub decoded: u64,
+        /// Blocks dropped by `decode_dedup` as already seen or no longer needed
+        pub duplicates: u64,
+        /// Successful `recover` calls
+        pub recoveries: u64,
+    }
+
+    impl Stats {
+        pub fn snapshot(&self) -> StatsSnapshot {
+            StatsSnapshot {
+                encoded: self.encoded.load(Ordering::Relaxed),
+                decoded: self.decoded.load(Ordering::Relaxed),
+                duplicates: self.duplicates.load(Ordering::Relaxed),
+                recoveries: self.recoveries.load(Ordering::Relaxed),
+            }
+        }
+
+        fn count(counter: &AtomicU64) {
+            counter.fetch_add(1, Ordering::Relaxed);
         }
     }
 
+    /// Encodes a message into blocks. The encoder keeps its own copy of the message, so the
+    /// slice passed to the constructors is only borrowed for the duration of the call.
     pub struct WirehairEncoder {
-        native_handler: *const c_void,
+        native_handler: NonNull<c_void>,
+        // wirehair_encoder_create doesn't copy the message, the codec keeps reading the original
+        // blocks through the pointer it was given (Codec::SetInput). Handing it a copy owned by the
+        // encoder ties the pointer's validity to the codec instead of to the caller's borrow.
+        // The copy is also what `Clone` rebuilds a codec from.
+        // No alignment is needed: gf256 only uses unaligned SIMD loads (_mm_loadu_si128,
+        // _mm256_loadu_si256, vld1q_u8), and GF256_ALIGNED_ACCESSES is defined but never checked.
+        message: Arc<[u8]>,
+        message_size: u64,
+        block_size: u32,
+        stats: Stats,
     }
 
+    // SAFETY: the native codec is a self-contained heap allocation which is only reachable
+    // through `native_handler`, so ownership may move between threads. It is deliberately not
+    // `Sync`: the library does no internal locking, so a codec must be used from one thread at a time.
+    unsafe impl Send for WirehairEncoder {}
+
     impl WirehairEncoder {
+        /// `message` must hold at least `message_size_bytes` bytes, otherwise `InvalidInput` is returned.
+        /// The first `message_size_bytes` are copied, `message` may be dropped or reused right after.
+        /// Prefer `from_slice`, which takes the size from the slice itself.
+        /// Takes the token of `wirehair_init` as proof that the library is initialized,
+        /// the other constructors initialize it themselves.
         pub fn new(
+            _init: &Initialized,
             message: &[u8],
             message_size_bytes: u64,
             block_size_bytes: u32,
-        ) -> WirehairEncoder {
-            WirehairEncoder {
-                native_handler: unsafe {
-                    wirehair_encoder_create(
-                        null::<c_void>(),
-                        message.as_ptr(),
-                        message_size_bytes,
-                        block_size_bytes,
-                    )
-                },
-            }
+        ) -> Result<WirehairEncoder> {
+            check_sizes(message_size_bytes, block_size_bytes)?;
+            check_message_len(message, message_size_bytes)?;
+
+            let message = Arc::from(&message[..message_size_bytes as usize]);
+
+            WirehairEncoder::create(null::<c_void>(), message, block_size_bytes)
+        }
+
+        /// Validates `params` before touching the library, so bad N is reported precisely
+        pub fn with_params(
+            message: &[u8],
+            params: FecParams,
+        ) -> Result<WirehairEncoder> {
+            params.validate()?;
+
+            WirehairEncoder::new(&wirehair_init()?, message, params.message_size, params.block_size)
+        }
+
+        pub fn from_slice(
+            message: &[u8],
+            block_size_bytes: u32,
+        ) -> Result<WirehairEncoder> {
+            let message_size_bytes =
+                u64::try_from(message.len()).map_err(|_| WirehairError::InvalidInput)?;
+
+            WirehairEncoder::new(&wirehair_init()?, message, message_size_bytes, block_size_bytes)
+        }
+
+        /// Same as `new` for sizes given as `usize`. Sizes the library can't represent are `InvalidInput`.
+        pub fn new_usize(
+            message: &[u8],
+            message_size_bytes: usize,
+            block_size_bytes: usize,
+        ) -> Result<WirehairEncoder> {
+            let (message_size_bytes, block_size_bytes) =
+                sizes_from_usize(message_size_bytes, block_size_bytes)?;
+
+            WirehairEncoder::new(&wirehair_init()?, message, message_size_bytes, block_size_bytes)
+        }
+
+        /// Same as `new`, but recycles the native allocation of `old` instead of allocating
+        /// a fresh codec, which saves large mallocs when encoding many same-sized messages.
+        pub fn new_reusing(
+            old: WirehairEncoder,
+            message: &[u8],
+            message_size_bytes: u64,
+            block_size_bytes: u32,
+        ) -> Result<WirehairEncoder> {
+            check_sizes(message_size_bytes, block_size_bytes)?;
+            check_message_len(message, message_size_bytes)?;
+
+            // The library takes the old codec over (it is even freed if creation fails),
+            // so its Drop must not run
+            let reuse_handler = old.into_handle().as_ptr();
+
+            let message = Arc::from(&message[..message_size_bytes as usize]);
+
+            WirehairEncoder::create(reuse_handler, message, block_size_bytes)
+        }
+
+        // Releases the message copy, the caller takes the native handle over
+        fn into_handle(self) -> NonNull<c_void> {
+            let mut encoder = ManuallyDrop::new(self);
+            unsafe { std::ptr::drop_in_place(&mut encoder.message) };
+
+            encoder.native_handler
+        }
+
+        fn create(
+            reuse_handler: *const c_void,
+            message: Arc<[u8]>,
+            block_size_bytes: u32,
+        ) -> Result<WirehairEncoder> {
+            let message_size_bytes = message.len() as u64;
+            let native_handler = unsafe {
+                wirehair_encoder_create(
+                    reuse_handler,
+                    message.as_ptr(),
+                    message_size_bytes,
+                    block_size_bytes,
+                )
+            };
+
+            // The library returns null on bad N, OOM or when wirehair_init was not called
+            let native_handler =
+                NonNull::new(native_handler as *mut c_void).ok_or(WirehairError::InvalidInput)?;
+
+            Ok(WirehairEncoder {
+                native_handler,
+                message,
+                message_size: message_size_bytes,
+                block_size: block_size_bytes,
+                stats: Stats::default(),
+            })
+        }
+
+        pub fn message_size(&self) -> u64 {
+            self.message_size
+        }
+
+        pub fn block_size(&self) -> u32 {
+            self.block_size
         }
 
+        pub fn stats(&self) -> &Stats {
+            &self.stats
+        }
+
+        pub(crate) fn message(&self) -> &[u8] {
+            &self.message
+        }
+
+        /// N = ceil(message_size / block_size), the number of original blocks a receiver's decoder is sized for
+        pub fn block_count(&self) -> u32 {
+            // The library refuses to create an encoder with N above MAX_BLOCK_COUNT, so this fits
+            self.message_size.div_ceil(u64::from(self.block_size)) as u32
+        }
+
+        /// The true length of the message, same as `message_size`
+        pub fn message_len(&self) -> u64 {
+            self.message_size
+        }
+
+        /// N * block_size, the message with its last block padded to a whole block.
+        /// The last block on the wire still only carries `message_len % block_size` valid bytes.
+        pub fn padded_len(&self) -> u64 {
+            u64::from(self.block_count()) * u64::from(self.block_size)
+        }
+
+        /// Writes block `block_id` into `block` and returns the number of valid bytes in it.
+        /// `block` must be able to hold a whole block (`block_size` bytes), otherwise `InvalidInput` is returned.
+        /// So are ids beyond u32, the library takes 32-bit ones.
+        /// The rest of the first `block_size` bytes is zeroed, so a reused buffer sent whole by
+        /// mistake doesn't leak what the previous block left there.
+        /// There is no retry on another id: the library only returns the seed errors while
+        /// setting the encoder up, never from encoding a block.
         pub fn encode(
             &self,
-            block_id: u64,
+            block_id: impl Into<BlockId>,
             block: &mut [u8],
-            block_size: u32,
-            block_out_bytes: &mut u32,
-        ) -> Result<WirehairResult, WirehairError> {
+        ) -> Result<u32> {
+            // The library only checks the buffer against the length of the block it is about to
+            // write, which it works out from the id truncated to 16 bits
+            if block.len() < self.block_size as usize {
+                return Err(WirehairError::InvalidInput);
+            }
+
+            // The library takes 32-bit ids, a larger one would be encoded as some other block
+            let block_id = block_id.into();
+            let native_id = c_uint::try_from(block_id.0).map_err(|_| WirehairError::InvalidInput)?;
+            let block_size = u32::try_from(block.len()).unwrap_or(u32::MAX);
+            let mut block_out_bytes: u32 = 0;
+
             let result = unsafe {
                 wirehair_encode(
-                    self.native_handler,
-                    block_id,
+                    self.native_handler.as_ptr(),
+                    native_id,
                     block.as_mut_ptr(),
                     block_size,
-                    block_out_bytes,
+                    &mut block_out_bytes,
                 )
             };
 
-            parse_wirehair_result(result)
+            let block_out_bytes = parse_wirehair_result(result).map(|_| block_out_bytes)?;
+            Stats::count(&self.stats.encoded);
+            // Only the short last original block leaves a tail
+            block[block_out_bytes as usize..self.block_size as usize].fill(0);
+            #[cfg(feature = "log")]
+            log::debug!("encoded block {}, {} bytes", block_id.0, block_out_bytes);
+
+            Ok(block_out_bytes)
         }
-    }
 
-    impl Drop for WirehairEncoder {
-        fn drop(&mut self) {
-            unsafe { wirehair_free(self.native_handler) };
+        /// The former `encode`, which reports the number of valid bytes through an out-parameter
+        #[deprecated(since = "0.1.4", note = "use `encode`, which returns the number of valid bytes")]
+        pub fn encode_out_bytes(
+            &self,
+            block_id: impl Into<BlockId>,
+            block: &mut [u8],
+            block_out_bytes: &mut u32,
+        ) -> Result<WirehairResult> {
+            *block_out_bytes = self.encode(block_id, block)?;
+
+            Ok(WirehairResult::Success)
         }
-    }
 
-    pub struct WirehairDecoder {
-        native_handler: *const c_void,
-    }
+        /// Same as `encode`, with the number of valid bytes as `usize`
+        pub fn encode_into(
+            &self,
+            block_id: impl Into<BlockId>,
+            out: &mut [u8],
+        ) -> Result<usize> {
+            self.encode(block_id, out).map(|len| len as usize)
+        }
 
-    impl WirehairDecoder {
-        pub fn new(message_size_bytes: u64, block_size_bytes: u32) -> WirehairDecoder {
-            WirehairDecoder {
-                native_handler: unsafe {
-                    wirehair_decoder_create(null::<c_void>(), message_size_bytes, block_size_bytes)
-                },
+        /// Encodes consecutive blocks from `start_id` on into `bufs`, one block per buffer, e.g. to
+        /// fill the messages of a `sendmmsg` call. Returns the valid bytes of each buffer.
+        /// Every buffer must hold `block_size` bytes, otherwise nothing is encoded and
+        /// `InvalidInput` is returned.
+        pub fn encode_batch(
+            &self,
+            start_id: impl Into<BlockId>,
+            bufs: &mut [&mut [u8]],
+        ) -> Result<Vec<usize>> {
+            if bufs.iter().any(|buf| buf.len() < self.block_size as usize) {
+                return Err(WirehairError::InvalidInput);
+            }
+
+            let start_id = start_id.into().0;
+            bufs.iter_mut()
+                .zip(start_id..)
+                .map(|(buf, block_id)| self.encode_into(block_id, buf))
+                .collect()
+        }
+
+        /// Original block `index`, i.e. block id `index`, which for `index < N` is the message
+        /// bytes `index * block_size..` as they are. Indexes of repair blocks are `InvalidInput`.
+        pub fn original_block(&self, index: u32) -> Result<Vec<u8>> {
+            if index >= self.block_count() {
+                return Err(WirehairError::InvalidInput);
             }
+
+            let mut block = vec![0u8; self.block_size as usize];
+            let len = self.encode_into(u64::from(index), &mut block)?;
+            block.truncate(len);
+
+            Ok(block)
         }
 
-        pub fn decode(
+        pub fn encode_packet(&self, block_id: impl Into<BlockId>) -> Result<Packet> {
+            let block_id = block_id.into();
+            let mut data = vec![0u8; self.block_size as usize];
+            let len = self.encode_into(block_id, &mut data)?;
+            data.truncate(len);
+
+            Ok(Packet {
+                block_id: block_id.0,
+                block_size: self.block_size,
+                message_size: self.message_size,
+                data,
+            })
+        }
+
+        /// Returns an endless iterator over encoded blocks starting from `start_id`.
+        /// Each item is a `(block_id, block)` pair with the block already truncated to its valid length.
+        pub fn blocks(&self, start_id: impl Into<BlockId>) -> BlockIter<'_> {
+            BlockIter {
+                encoder: self,
+                block_id: start_id.into().0,
+                buffer: vec![0u8; self.block_size as usize],
+            }
+        }
+
+        /// Like `blocks`, but every block is written into `buffer`, so a stream of blocks needs no
+        /// allocations at all. `buffer` must be able to hold a whole block.
+        pub fn blocks_into<'b>(
             &self,
-            block_id: u64,
-            block: &[u8],
-            block_out_size_bytes: u32,
-        ) -> Result<WirehairResult, WirehairError> {
-            let result = unsafe {
-                wirehair_decode(
-                    self.native_handler,
-                    block_id,
-                    block.as_ptr(),
-                    block_out_size_bytes,
-                )
-            };
+            buffer: &'b mut [u8],
+            start_id: impl Into<BlockId>,
+        ) -> BlocksInto<'_, 'b> {
+            BlocksInto {
+                encoder: self,
+                buffer,
+                block_id: start_id.into().0,
+            }
+        }
+
+        /// Yields exactly the blocks with ids in `ids`, e.g. to repeat the ones a peer reported missing
+        pub fn encode_range(
+            &self,
+            ids: Range<u64>,
+        ) -> impl Iterator<Item = Result<(BlockId, Vec<u8>)>> + '_ {
+            let count = ids.end.saturating_sub(ids.start);
 
-            parse_wirehair_result(result)
+            self.blocks(ids.start).take(usize::try_from(count).unwrap_or(usize::MAX))
         }
 
-        pub fn recover(
+        /// Yields the blocks from id 0 on that should get the message through a channel losing
+        /// `loss_rate` of them: N / (1 - loss_rate) rounded up, plus LOSS_EXTRA_BLOCKS for the
+        /// codec itself. `loss_rate` must be in `0.0..1.0`, otherwise `InvalidInput` is returned.
+        pub fn blocks_for_loss(
             &self,
-            message: &mut [u8],
-            message_size_bytes: u64,
-        ) -> Result<WirehairResult, WirehairError> {
-            let result = unsafe {
-                wirehair_recover(
-                    self.native_handler,
-                    message.as_mut_ptr(),
-                    message_size_bytes,
-                )
-            };
+            loss_rate: f64,
+        ) -> Result<impl Iterator<Item = Result<(BlockId, Vec<u8>)>> + '_> {
+            if !(0.0..1.0).contains(&loss_rate) {
+                return Err(WirehairError::InvalidInput);
+            }
 
-            parse_wirehair_result(result)
+            let expected = (f64::from(self.block_count()) / (1.0 - loss_rate)).ceil() as u64;
+            // The library takes 32-bit ids, there is nothing to send past them
+            let count = (expected + LOSS_EXTRA_BLOCKS).min(u64::from(u32::MAX) + 1);
+
+            Ok(self.encode_range(0..count))
         }
-    }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::wirehair::*;
+        /// Yields the blocks `0..count` in an order shuffled by `seed`, so originals and repair blocks
+        /// are mixed like on a real network. The same seed always gives the same order: a Fisher-Yates
+        /// shuffle driven by splitmix64 seeded with `seed`.
+        pub fn scheduled_blocks(
+            &self,
+            seed: u64,
+            count: u32,
+        ) -> impl Iterator<Item = Result<(BlockId, Vec<u8>)>> + '_ {
+            let mut ids: Vec<u64> = (0..u64::from(count)).collect();
+            let mut state = seed;
+            for i in (1..ids.len()).rev() {
+                let j = splitmix64(&mut state) % (i as u64 + 1);
+                ids.swap(i, j as usize);
+            }
 
-    #[test]
-    fn basic_flow_works() {
-        assert!(wirehair_init().is_ok());
+            let mut buffer = vec![0u8; self.block_size as usize];
+            ids.into_iter().map(move |block_id| {
+                let len = self.encode_into(block_id, &mut buffer)?;
 
-        let mut message = [0u8; 500];
-        for i in 0..500 {
-            message[i] = i as u8
+                Ok((BlockId(block_id), buffer[..len].to_vec()))
+            })
         }
 
-        let encoder = WirehairEncoder::new(&message, 500, 50);
-        let decoder = WirehairDecoder::new(500, 50);
+        /// Encodes blocks `0..count` at once, each truncated to its valid length
+        pub fn encode_all(&self, count: u32) -> Result<Vec<(BlockId, Vec<u8>)>> {
+            self.blocks(0).take(count as usize).collect()
+        }
 
-        let mut block_id = 0;
+        /// Writes the blocks `ids` to `w`, each framed as its id (u64 LE), its valid length
+        /// (u32 LE) and the valid bytes. `WirehairDecoder::decode_from_reader` reads them back.
+        pub fn encode_to_writer<W: Write>(&self, ids: Range<u64>, w: &mut W) -> io::Result<()> {
+            let mut block = vec![0u8; self.block_size as usize];
 
-        loop {
-            let mut block = [0u8; 50];
-            let mut block_out_bytes: u32 = 0;
-            let result = encoder.encode(block_id, &mut block, 50, &mut block_out_bytes);
-            assert!(result.is_ok());
+            for block_id in ids {
+                let len = self.encode(block_id, &mut block)?;
 
-            if block_id % 5 == 0 {
-                block_id += 1;
-                continue;
+                w.write_all(&block_id.to_le_bytes())?;
+                w.write_all(&len.to_le_bytes())?;
+                w.write_all(&block[..len as usize])?;
             }
 
-            let result = decoder.decode(block_id, &block, block_out_bytes);
-            assert!(result.is_ok());
+            Ok(())
+        }
+    }
 
-            block_id += 1;
+    // Block id and valid length in front of every block of `encode_to_writer`
+    const FRAME_HEADER_SIZE: usize = 12;
 
-            match result.unwrap() {
-                WirehairResult::NeedMore => continue,
-                WirehairResult::Success => break,
-                _ => panic!(),
+    // Fills `header` from `r`, `Ok(false)` if `r` ended right before it
+    fn read_frame_header<R: Read>(r: &mut R, header: &mut [u8; FRAME_HEADER_SIZE]) -> io::Result<bool> {
+        let mut filled = 0;
+        while filled < header.len() {
+            match r.read(&mut header[filled..]) {
+                Ok(0) if filled == 0 => return Ok(false),
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(read) => filled += read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
             }
         }
 
-        let mut decoded_message = [0u8; 500];
+        Ok(true)
+    }
 
-        let result = decoder.recover(&mut decoded_message, 500);
-        assert!(result.is_ok());
+    // splitmix64, small and well known, so a schedule is easy to reproduce in other implementations
+    fn splitmix64(state: &mut u64) -> u64 {
+        *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut z = *state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // N = ceil(message_size / block_size) divides by the block size, so zeros never reach the library.
+    // The message also has to fit into memory, which only matters on 32-bit targets.
+    fn check_sizes(message_size_bytes: u64, block_size_bytes: u32) -> Result<()> {
+        if message_size_bytes == 0 || block_size_bytes == 0 {
+            return Err(WirehairError::InvalidInput);
+        }
+        if usize::try_from(message_size_bytes).is_err() {
+            return Err(WirehairError::InvalidInput);
+        }
+
+        Ok(())
+    }
+
+    // Sizes as they come from slices and buffers, checked instead of silently truncated
+    fn sizes_from_usize(
+        message_size_bytes: usize,
+        block_size_bytes: usize,
+    ) -> Result<(u64, u32)> {
+        let message_size_bytes =
+            u64::try_from(message_size_bytes).map_err(|_| WirehairError::InvalidInput)?;
+        let block_size_bytes =
+            u32::try_from(block_size_bytes).map_err(|_| WirehairError::InvalidInput)?;
+
+        Ok((message_size_bytes, block_size_bytes))
+    }
+
+    // Blocks the decode helpers consume before giving up. With distinct blocks wirehair nearly always
+    // solves within N + 2, the slack is for duplicates and junk mixed into the stream.
+    fn default_max_blocks(message_size_bytes: u64, block_size_bytes: u32) -> u32 {
+        let block_count = FecParams::new(message_size_bytes, block_size_bytes)
+            .validate()
+            .unwrap_or(MAX_BLOCK_COUNT);
+
+        block_count.saturating_add(block_count / 2).saturating_add(32)
+    }
+
+    /// Blocks `blocks_for_loss` adds on top of the expected losses. Wirehair recovers from
+    /// N blocks most of the time and from N + 2 nearly always.
+    pub const LOSS_EXTRA_BLOCKS: u64 = 2;
+
+    // The library reads `message_size_bytes` from the message pointer, so a shorter slice is UB
+    fn check_message_len(message: &[u8], message_size_bytes: u64) -> Result<()> {
+        match u64::try_from(message.len()) {
+            Ok(len) if len >= message_size_bytes => Ok(()),
+            _ => Err(WirehairError::InvalidInput),
+        }
+    }
+
+    pub struct BlockIter<'a> {
+        encoder: &'a WirehairEncoder,
+        block_id: u64,
+        buffer: Vec<u8>,
+    }
+
+    impl<'a> Iterator for BlockIter<'a> {
+        type Item = Result<(BlockId, Vec<u8>)>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            let block_id = BlockId(self.block_id);
+            self.block_id += 1;
+
+            let result = self.encoder.encode_into(block_id, &mut self.buffer);
+
+            Some(result.map(|len| (block_id, self.buffer[..len].to_vec())))
+        }
+    }
+
+    pub struct BlocksInto<'a, 'b> {
+        encoder: &'a WirehairEncoder,
+        buffer: &'b mut [u8],
+        block_id: u64,
+    }
+
+    impl<'a, 'b> BlocksInto<'a, 'b> {
+        /// Encodes the next block into the buffer and returns its id and valid bytes.
+        /// The returned slice borrows the buffer, so it has to be released before the next call.
+        pub fn next_block(&mut self) -> Result<(BlockId, &[u8])> {
+            let block_id = BlockId(self.block_id);
+            self.block_id += 1;
+
+            let len = self.encoder.encode_into(block_id, self.buffer)?;
+
+            Ok((block_id, &self.buffer[..len]))
+        }
+    }
+
+    // The handle is opaque and never null, so it isn't worth printing
+    impl Debug for WirehairEncoder {
+        fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+            f.debug_struct("WirehairEncoder")
+                .field("message_size", &self.message_size)
+                .field("block_size", &self.block_size)
+                .finish()
+        }
+    }
+
+    /// Builds a fresh native codec over the same message, which costs as much as `new`.
+    /// The message bytes themselves are shared, not copied.
+    impl Clone for WirehairEncoder {
+        fn clone(&self) -> WirehairEncoder {
+            // The parameters were accepted once already, so only an allocation failure can get here
+            WirehairEncoder::create(null::<c_void>(), self.message.clone(), self.block_size)
+                .expect("failed to create a native encoder for the clone")
+        }
+    }
+
+    impl Drop for WirehairEncoder {
+        // A failed create is an `Err`, never a codec holding null, so the handle is always live here
+        fn drop(&mut self) {
+            unsafe { wirehair_free(self.native_handler.as_ptr()) };
+        }
+    }
+
+    /// Turns a solved decoder into an encoder for the same message, e.g. to relay it further.
+    /// The native codec is converted in place, only the message is copied out.
+    impl TryFrom<WirehairDecoder> for WirehairEncoder {
+        type Error = WirehairError;
+
+        fn try_from(decoder: WirehairDecoder) -> Result<WirehairEncoder> {
+            // The encoder keeps its own copy of the message for `Clone`, so recover it while the
+            // codec is still a decoder. An unsolved decoder is refused with `NeedMore` right here.
+            let message = decoder.recover_vec()?;
+            let native_handler = decoder.native_handler.ok_or(WirehairError::OOM)?;
+
+            let result = unsafe { wirehair_decoder_becomes_encoder(native_handler.as_ptr()) };
+            parse_wirehair_result(result)?;
+
+            // The handle now belongs to the encoder, so the decoder must not free it on drop
+            let (message_size, block_size) = (decoder.message_size, decoder.block_size);
+            decoder.into_handle();
+
+            Ok(WirehairEncoder {
+                native_handler,
+                message: message.into(),
+                message_size,
+                block_size,
+                stats: Stats::default(),
+            })
+        }
+    }
+
+    /// An encoder built from a message the caller hands over, for messages which are produced
+    /// as a `Vec` anyway. Derefs to `WirehairEncoder` for all the encode methods.
+    #[derive(Debug, Clone)]
+    pub struct OwnedEncoder {
+        encoder: WirehairEncoder,
+    }
+
+    impl OwnedEncoder {
+        pub fn new(message: Vec<u8>, block_size_bytes: u32) -> Result<OwnedEncoder> {
+            let message_size_bytes =
+                u64::try_from(message.len()).map_err(|_| WirehairError::InvalidInput)?;
+            check_sizes(message_size_bytes, block_size_bytes)?;
+            wirehair_init()?;
+
+            let encoder = WirehairEncoder::create(null::<c_void>(), message.into(), block_size_bytes)?;
+
+            Ok(OwnedEncoder { encoder })
+        }
+
+        pub fn message(&self) -> &[u8] {
+            &self.encoder.message
+        }
+
+        pub fn into_encoder(self) -> WirehairEncoder {
+            self.encoder
+        }
+    }
+
+    impl Deref for OwnedEncoder {
+        type Target = WirehairEncoder;
+
+        fn deref(&self) -> &WirehairEncoder {
+            &self.encoder
+        }
+    }
+
+    /// An encoder shared between threads or tasks. `WirehairEncoder` is not `Sync`, so every
+    /// `encode` locks it, clones are handles to the same encoder.
+    #[derive(Debug, Clone)]
+    pub struct SharedEncoder(Arc<Mutex<WirehairEncoder>>);
+
+    impl SharedEncoder {
+        pub fn new(encoder: WirehairEncoder) -> SharedEncoder {
+            SharedEncoder(Arc::new(Mutex::new(encoder)))
+        }
+
+        /// Same as `WirehairEncoder::encode_into`, under the lock
+        pub fn encode(&self, block_id: impl Into<BlockId>, out: &mut [u8]) -> Result<usize> {
+            self.0.lock().unwrap().encode_into(block_id, out)
+        }
+    }
+
+    /// An encoder whose blocks are returned as `[u8; B]` arrays on the stack, so encoding
+    /// doesn't allocate. `B` has to be the block size of the encoder.
+    #[derive(Debug)]
+    pub struct FixedEncoder<const B: usize> {
+        encoder: WirehairEncoder,
+    }
+
+    impl<const B: usize> FixedEncoder<B> {
+        /// Fails with `InvalidInput` unless the block size of `encoder` is `B`
+        pub fn new(encoder: WirehairEncoder) -> Result<FixedEncoder<B>> {
+            if encoder.block_size() as usize != B {
+                return Err(WirehairError::InvalidInput);
+            }
+
+            Ok(FixedEncoder { encoder })
+        }
+
+        /// An encoder over `message` with blocks of `B` bytes
+        pub fn from_slice(message: &[u8]) -> Result<FixedEncoder<B>> {
+            let block_size = u32::try_from(B).map_err(|_| WirehairError::InvalidInput)?;
+
+            FixedEncoder::new(WirehairEncoder::from_slice(message, block_size)?)
+        }
+
+        pub fn encoder(&self) -> &WirehairEncoder {
+            &self.encoder
+        }
+
+        /// Block `block_id` and the number of its valid bytes
+        pub fn encode(&self, block_id: impl Into<BlockId>) -> Result<([u8; B], usize)> {
+            let mut block = [0u8; B];
+            let len = self.encoder.encode_into(block_id, &mut block)?;
+
+            Ok((block, len))
+        }
+    }
+
+    /// Collects a message written through `io::Write`, e.g. by a serializer, and turns it into an encoder
+    #[derive(Debug, Default)]
+    pub struct EncoderBuilder {
+        message: Vec<u8>,
+    }
+
+    impl EncoderBuilder {
+        pub fn new() -> EncoderBuilder {
+            EncoderBuilder::default()
+        }
+
+        pub fn finish(self, block_size_bytes: u32) -> Result<WirehairEncoder> {
+            OwnedEncoder::new(self.message, block_size_bytes).map(OwnedEncoder::into_encoder)
+        }
+    }
+
+    impl io::Write for EncoderBuilder {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.message.extend_from_slice(buf);
+
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// State of a decoder after a block, as returned by `WirehairDecoder::decode_progress`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct DecodeProgress {
+        pub complete: bool,
+        pub blocks_received: u32,
+        /// Estimated blocks still missing (see `blocks_remaining_estimate`), 0 once complete
+        pub blocks_needed: u32,
+    }
+
+    pub struct WirehairDecoder {
+        // None only after a `reset` which ran out of memory, the old codec is gone by then
+        native_handler: Option<NonNull<c_void>>,
+        message_size: u64,
+        block_size: u32,
+        blocks_received: Cell<u32>,
+        // The library recovers garbage from an unsolved decoder, so whether `decode` has
+        // returned `Success` yet is tracked here
+        solved: Cell<bool>,
+        // Copies of the original blocks received so far by id, if `retain_original_blocks` was called
+        retained_originals: Option<RefCell<HashMap<u32, Vec<u8>>>>,
+        // Ids accepted through `decode_dedup`
+        seen: RefCell<HashSet<u64>>,
+        // Bit per original block id, set once the block was accepted. Sized at creation,
+        // so decoding doesn't allocate.
+        originals_received: RefCell<Vec<u64>>,
+        stats: Stats,
+    }
+
+    impl Debug for WirehairDecoder {
+        fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+            f.debug_struct("WirehairDecoder")
+                .field("message_size", &self.message_size)
+                .field("block_size", &self.block_size)
+                .field("blocks_received", &self.blocks_received.get())
+                .finish()
+        }
+    }
+
+    impl Drop for WirehairDecoder {
+        // A failed create is an `Err` and a failed `reset` leaves `None`, so null is never freed
+        fn drop(&mut self) {
+            if let Some(native_handler) = self.native_handler {
+                unsafe { wirehair_free(native_handler.as_ptr()) };
+            }
+        }
+    }
+
+    // SAFETY: same reasoning as for `WirehairEncoder`. `decode` mutates the native state behind
+    // a shared reference, so sharing a decoder between threads (`Sync`) would be a data race.
+    unsafe impl Send for WirehairDecoder {}
+
+    impl WirehairDecoder {
+        /// Takes the token of `wirehair_init` as proof that the library is initialized.
+        /// The other constructors initialize it themselves.
+        pub fn new(
+            _init: &Initialized,
+            message_size_bytes: u64,
+            block_size_bytes: u32,
+        ) -> Result<WirehairDecoder> {
+            check_sizes(message_size_bytes, block_size_bytes)?;
+
+            WirehairDecoder::create(null::<c_void>(), message_size_bytes, block_size_bytes)
+        }
+
+        /// Validates `params` before touching the library, so bad N is reported precisely
+        pub fn with_params(params: FecParams) -> Result<WirehairDecoder> {
+            params.validate()?;
+
+            WirehairDecoder::new(&wirehair_init()?, params.message_size, params.block_size)
+        }
+
+        /// Same as `new`, but refuses sizes with N above `max_blocks` with `BadInputLargeN` before
+        /// anything is allocated. Meant for sizes announced by untrusted peers.
+        pub fn new_limited(
+            message_size_bytes: u64,
+            block_size_bytes: u32,
+            max_blocks: u32,
+        ) -> Result<WirehairDecoder> {
+            let block_count = FecParams::new(message_size_bytes, block_size_bytes).validate()?;
+            if block_count > max_blocks {
+                return Err(WirehairError::BadInputLargeN);
+            }
+
+            WirehairDecoder::new(&wirehair_init()?, message_size_bytes, block_size_bytes)
+        }
+
+        /// Same as `new` for sizes given as `usize`. Sizes the library can't represent are `InvalidInput`.
+        pub fn new_usize(
+            message_size_bytes: usize,
+            block_size_bytes: usize,
+        ) -> Result<WirehairDecoder> {
+            let (message_size_bytes, block_size_bytes) =
+                sizes_from_usize(message_size_bytes, block_size_bytes)?;
+
+            WirehairDecoder::new(&wirehair_init()?, message_size_bytes, block_size_bytes)
+        }
+
+        /// Same as `new`, but recycles the native allocation of `old`
+        pub fn new_reusing(
+            old: WirehairDecoder,
+            message_size_bytes: u64,
+            block_size_bytes: u32,
+        ) -> Result<WirehairDecoder> {
+            check_sizes(message_size_bytes, block_size_bytes)?;
+
+            // Taken over by the library just like in `WirehairEncoder::new_reusing`
+            let reuse_handler = old.into_handle().map_or(null_mut(), NonNull::as_ptr);
+
+            WirehairDecoder::create(reuse_handler, message_size_bytes, block_size_bytes)
+        }
+
+        fn create(
+            reuse_handler: *const c_void,
+            message_size_bytes: u64,
+            block_size_bytes: u32,
+        ) -> Result<WirehairDecoder> {
+            let native_handler = unsafe {
+                wirehair_decoder_create(reuse_handler, message_size_bytes, block_size_bytes)
+            };
+            let native_handler =
+                NonNull::new(native_handler as *mut c_void).ok_or(WirehairError::InvalidInput)?;
+            let block_count = message_size_bytes.div_ceil(u64::from(block_size_bytes));
+
+            Ok(WirehairDecoder {
+                native_handler: Some(native_handler),
+                message_size: message_size_bytes,
+                block_size: block_size_bytes,
+                blocks_received: Cell::new(0),
+                solved: Cell::new(false),
+                retained_originals: None,
+                seen: RefCell::new(HashSet::new()),
+                originals_received: RefCell::new(vec![0; block_count.div_ceil(64) as usize]),
+                stats: Stats::default(),
+            })
+        }
+
+        /// Returns the decoder to its empty state for the next message of the same size,
+        /// keeping the native allocation. If the library runs out of memory doing so, `OOM` is
+        /// returned and the decoder is left without a codec: everything fails with `OOM`
+        /// until a later `reset` succeeds.
+        pub fn reset(&mut self) -> Result<()> {
+            // The library frees the old codec if it fails, so it is given up here already
+            let reuse_handler = self.native_handler.take().map_or(null_mut(), NonNull::as_ptr);
+            let native_handler = unsafe {
+                wirehair_decoder_create(reuse_handler, self.message_size, self.block_size)
+            };
+            self.blocks_received.set(0);
+            self.solved.set(false);
+            self.seen.get_mut().clear();
+            self.originals_received.get_mut().iter_mut().for_each(|bits| *bits = 0);
+            if let Some(retained_originals) = &self.retained_originals {
+                retained_originals.borrow_mut().clear();
+            }
+
+            // The sizes were accepted once already, so only OOM can fail here
+            let native_handler =
+                NonNull::new(native_handler as *mut c_void).ok_or(WirehairError::OOM)?;
+            self.native_handler = Some(native_handler);
+
+            Ok(())
+        }
+
+        // Releases everything but the native handle, which the caller takes over.
+        // Every field owning memory has to be emptied here, `forget` skips their destructors.
+        fn into_handle(mut self) -> Option<NonNull<c_void>> {
+            self.retained_originals = None;
+            self.seen = RefCell::default();
+            self.originals_received = RefCell::default();
+            let native_handler = self.native_handler;
+            std::mem::forget(self);
+
+            native_handler
+        }
+
+        // The native codec, `OOM` if a failed `reset` left the decoder without one
+        fn handle(&self) -> Result<*mut c_void> {
+            self.native_handler.map(NonNull::as_ptr).ok_or(WirehairError::OOM)
+        }
+
+        pub fn message_size(&self) -> u64 {
+            self.message_size
+        }
+
+        pub fn block_size(&self) -> u32 {
+            self.block_size
+        }
+
+        pub fn stats(&self) -> &Stats {
+            &self.stats
+        }
+
+        /// Makes the decoder keep a copy of every original block (`block_id < N`) it accepts from
+        /// now on, so `recovered_block` can return them before the message is solved.
+        /// Costs up to `message_size` bytes on top of the native decoder.
+        pub fn retain_original_blocks(&mut self) {
+            if self.retained_originals.is_none() {
+                self.retained_originals = Some(RefCell::new(HashMap::new()));
+            }
+        }
+
+        /// Returns original block `index` (`< N`) with its valid bytes. Any block can be recovered
+        /// once the message is solved, before that only retained copies of blocks received directly.
+        pub fn recovered_block(&self, index: u32) -> Option<Vec<u8>> {
+            if !self.solved.get() {
+                let retained_originals = self.retained_originals.as_ref()?.borrow();
+
+                return retained_originals.get(&index).cloned();
+            }
+
+            // wirehair_recover_block takes the index as a 16 bit number, so larger ones must not reach it
+            let block_count = FecParams::new(self.message_size, self.block_size).validate().ok()?;
+            if index >= block_count {
+                return None;
+            }
+
+            let mut block = vec![0u8; self.block_size as usize];
+            let mut block_out_bytes: u32 = 0;
+            let result = unsafe {
+                wirehair_recover_block(
+                    self.handle().ok()?,
+                    index,
+                    block.as_mut_ptr(),
+                    &mut block_out_bytes,
+                )
+            };
+            parse_wirehair_result(result).ok()?;
+            block.truncate(block_out_bytes as usize);
+
+            Some(block)
+        }
+
+        /// Number of blocks accepted by the decoder so far, i.e. `decode` calls which
+        /// returned `NeedMore` or `Success`. Counted on the Rust side, since the library
+        /// does not expose its internal row count.
+        pub fn blocks_received(&self) -> u32 {
+            self.blocks_received.get()
+        }
+
+        /// Reception overhead, the number of accepted blocks divided by N, once the decoder is solved.
+        /// `None` while more blocks are needed.
+        pub fn overhead(&self) -> Option<f64> {
+            if !self.solved.get() {
+                return None;
+            }
+
+            let block_count = FecParams::new(self.message_size, self.block_size)
+                .validate()
+                .ok()?;
+
+            Some(f64::from(self.blocks_received.get()) / f64::from(block_count))
+        }
+
+        /// How many more blocks are likely needed, N minus the blocks accepted so far.
+        /// `None` once the decoder is solved.
+        pub fn blocks_remaining_estimate(&self) -> Option<u32> {
+            if self.solved.get() {
+                return None;
+            }
+
+            let block_count = FecParams::new(self.message_size, self.block_size)
+                .validate()
+                .ok()?;
+
+            // Past N an unlucky decoder still needs at least one more block
+            Some(block_count.saturating_sub(self.blocks_received.get()).max(1))
+        }
+
+        /// Feeds a block to the decoder. Once it has returned `Success` the decoder is solved and
+        /// every further call returns `Success` right away, without passing the block to the library.
+        pub fn decode(
+            &self,
+            block_id: impl Into<BlockId>,
+            block: &[u8],
+            block_out_size_bytes: u32,
+        ) -> Result<WirehairResult> {
+            // The native codec isn't meant to be fed past the point where it solved the message
+            if self.solved.get() {
+                return Ok(WirehairResult::Success);
+            }
+
+            // The library reads `block_out_size_bytes` from the pointer and trusts it to be at most
+            // a block, so a length declared by an untrusted sender must be checked here
+            if block_out_size_bytes as usize > block.len() || block_out_size_bytes > self.block_size {
+                return Err(WirehairError::InvalidInput);
+            }
+
+            // The library takes 32-bit ids, a larger one would be decoded as some other block
+            let block_id = block_id.into();
+            let native_id = c_uint::try_from(block_id.0).map_err(|_| WirehairError::InvalidInput)?;
+
+            let result = unsafe {
+                wirehair_decode(
+                    self.handle()?,
+                    native_id,
+                    block.as_ptr(),
+                    block_out_size_bytes,
+                )
+            };
+
+            let result = parse_wirehair_result(result);
+            if result.is_ok() {
+                self.blocks_received.set(self.blocks_received.get() + 1);
+                Stats::count(&self.stats.decoded);
+                self.mark_received(block_id);
+                self.retain(block_id, &block[..block_out_size_bytes as usize]);
+                #[cfg(feature = "log")]
+                log::debug!("accepted block {}, {} bytes", block_id.0, block_out_size_bytes);
+            }
+            if result == Ok(WirehairResult::Success) {
+                self.solved.set(true);
+            }
+
+            result
+        }
+
+        fn mark_received(&self, block_id: BlockId) {
+            let block_count = self.message_size.div_ceil(u64::from(self.block_size));
+            if block_id.0 < block_count {
+                let mut originals_received = self.originals_received.borrow_mut();
+                originals_received[(block_id.0 / 64) as usize] |= 1 << (block_id.0 % 64);
+            }
+        }
+
+        /// Original block ids (below N) which haven't been accepted yet, in ascending order,
+        /// e.g. for asking a sender of original blocks to resend them. Empty once the decoder
+        /// is solved, since every original block can be recovered then.
+        pub fn missing_original_ids(&self) -> Vec<u64> {
+            if self.solved.get() {
+                return Vec::new();
+            }
+
+            let block_count = self.message_size.div_ceil(u64::from(self.block_size));
+            let originals_received = self.originals_received.borrow();
+
+            (0..block_count)
+                .filter(|id| originals_received[(id / 64) as usize] & (1 << (id % 64)) == 0)
+                .collect()
+        }
+
+        fn retain(&self, block_id: BlockId, block: &[u8]) {
+            let retained_originals = match &self.retained_originals {
+                Some(retained_originals) => retained_originals,
+                None => return,
+            };
+
+            let is_original = FecParams::new(self.message_size, self.block_size)
+                .validate()
+                .is_ok_and(|block_count| block_id.0 < u64::from(block_count));
+            if is_original {
+                retained_originals
+                    .borrow_mut()
+                    .entry(block_id.0 as u32)
+                    .or_insert_with(|| block.to_vec());
+            }
+        }
+
+        /// Same as `feed`, but remembers the ids it has accepted and answers a repeated id with
+        /// the current state without passing the block to the library again. Meant for networks
+        /// which duplicate and reorder packets. Keeps about 8 bytes (plus hash set overhead)
+        /// per accepted block until the decoder is reset or dropped.
+        pub fn decode_dedup(&self, block_id: impl Into<BlockId>, block: &[u8]) -> Result<bool> {
+            let block_id = block_id.into();
+            if self.solved.get() || self.seen.borrow().contains(&block_id.0) {
+                #[cfg(feature = "log")]
+                log::debug!("duplicate block {}", block_id.0);
+                Stats::count(&self.stats.duplicates);
+                return Ok(self.solved.get());
+            }
+
+            let solved = self.feed(block_id, block)?;
+            self.seen.borrow_mut().insert(block_id.0);
+
+            Ok(solved)
+        }
+
+        /// Decodes a whole block and returns whether the message can be recovered now
+        pub fn feed(&self, block_id: impl Into<BlockId>, block: &[u8]) -> Result<bool> {
+            let result = self.decode(block_id, block, block.len() as u32)?;
+
+            Ok(result == WirehairResult::Success)
+        }
+
+        /// Decodes a whole block like `feed` and returns the progress made so far
+        pub fn decode_progress(
+            &self,
+            block_id: impl Into<BlockId>,
+            block: &[u8],
+        ) -> Result<DecodeProgress> {
+            let complete = self.feed(block_id, block)?;
+
+            Ok(DecodeProgress {
+                complete,
+                blocks_received: self.blocks_received(),
+                blocks_needed: self.blocks_remaining_estimate().unwrap_or(0),
+            })
+        }
+
+        /// Feeds the given `(block_id, block)` pairs until the decoder is solved and ignores the rest.
+        /// Returns whether it is solved, i.e. `false` if the blocks ran out first, and fails with
+        /// `ExtraInsufficient` once far more blocks than N were consumed in one call without
+        /// solving, e.g. an endless stream of duplicates.
+        pub fn decode_many<'a, I, B>(&self, blocks: I) -> Result<bool>
+        where
+            I: IntoIterator<Item = (B, &'a [u8])>,
+            B: Into<BlockId>,
+        {
+            if self.solved.get() {
+                return Ok(true);
+            }
+
+            let max_blocks = default_max_blocks(self.message_size, self.block_size);
+            for (consumed, (block_id, block)) in blocks.into_iter().enumerate() {
+                if consumed as u64 >= u64::from(max_blocks) {
+                    return Err(WirehairError::ExtraInsufficient);
+                }
+                if self.feed(block_id, block)? {
+                    return Ok(true);
+                }
+            }
+
+            Ok(false)
+        }
+
+        /// Reads blocks framed by `WirehairEncoder::encode_to_writer` from `r` until the decoder
+        /// is solved and returns whether it is, i.e. `false` if `r` ended first. Stops reading
+        /// right after the block which solved it. A frame longer than a block is `InvalidInput`,
+        /// a frame cut short is `UnexpectedEof`, and like `decode_many` it gives up with
+        /// `ExtraInsufficient` once far more blocks than N were read.
+        pub fn decode_from_reader<R: Read>(&self, r: &mut R) -> io::Result<bool> {
+            if self.solved.get() {
+                return Ok(true);
+            }
+
+            let max_blocks = default_max_blocks(self.message_size, self.block_size);
+            let mut header = [0u8; FRAME_HEADER_SIZE];
+            let mut block = vec![0u8; self.block_size as usize];
+
+            for _ in 0..max_blocks {
+                if !read_frame_header(r, &mut header)? {
+                    return Ok(false);
+                }
+
+                let (block_id, len) = header.split_at(8);
+                let block_id = u64::from_le_bytes(<[u8; 8]>::try_from(block_id).unwrap());
+                let len = u32::from_le_bytes(<[u8; 4]>::try_from(len).unwrap());
+                // Checked before reading, the length comes from the stream
+                if len > self.block_size {
+                    return Err(WirehairError::InvalidInput.into());
+                }
+
+                let block = &mut block[..len as usize];
+                r.read_exact(block)?;
+                if self.feed(block_id, block)? {
+                    return Ok(true);
+                }
+            }
+
+            Err(WirehairError::ExtraInsufficient.into())
+        }
+
+        /// Decodes `packet`, rejecting packets encoded with other sizes than this decoder's
+        pub fn decode_packet(&self, packet: &Packet) -> Result<WirehairResult> {
+            if packet.block_size != self.block_size || packet.message_size != self.message_size {
+                return Err(WirehairError::InvalidInput);
+            }
+
+            self.decode(packet.block_id, &packet.data, packet.data.len() as u32)
+        }
+
+        /// `message` must hold at least `message_size` bytes, otherwise `InvalidInput` is returned.
+        /// Fails with `NeedMore` until `decode` has returned `Success`, leaving `message` alone.
+        pub fn recover(&self, message: &mut [u8]) -> Result<()> {
+            // The library reconstructs the message from whatever it has been fed without telling
+            // whether it is solved, so an early call would return garbage
+            if !self.solved.get() {
+                return Err(WirehairError::NeedMore);
+            }
+
+            // The library writes `message_size` bytes no matter how large the buffer is
+            check_message_len(message, self.message_size)?;
+
+            let result = unsafe {
+                wirehair_recover(self.handle()?, message.as_mut_ptr(), self.message_size)
+            };
+            if parse_wirehair_result(result)? != WirehairResult::Success {
+                return Err(WirehairError::Error);
+            }
+
+            Stats::count(&self.stats.recoveries);
+            #[cfg(feature = "log")]
+            log::info!(
+                "recovered {} bytes from {} blocks, overhead {:.3}",
+                self.message_size,
+                self.blocks_received.get(),
+                self.overhead().unwrap_or(f64::NAN)
+            );
+
+            Ok(())
+        }
+
+        /// Same as `recover`, returning the bytes of `out` which hold the message. That's always
+        /// `message_size`, the last block's padding isn't written, so `&out[..len]` is the message
+        /// even if `out` was sized for whole blocks. Fails with `NeedMore` until solved, like
+        /// `recover`.
+        pub fn recover_len(&self, out: &mut [u8]) -> Result<usize> {
+            self.recover(out)?;
+
+            Ok(self.message_size as usize)
+        }
+
+        /// An alias of `recover`, which is just as strict about `solved` now
+        pub fn finalize(&self, out: &mut [u8]) -> Result<()> {
+            self.recover(out)
+        }
+
+        /// Recovers the message into `message` like `recover`, but reports a decoder which needs
+        /// more blocks as `Ok(None)` instead of recovering garbage from it
+        pub fn try_recover(&self, message: &mut [u8]) -> Result<Option<()>> {
+            if !self.solved.get() {
+                return Ok(None);
+            }
+
+            self.recover(message).map(Some)
+        }
+
+        /// Allocates a buffer of `message_size` bytes and recovers the message into it.
+        /// Fails with `NeedMore` until `decode` has returned `Success`.
+        pub fn recover_vec(&self) -> Result<Vec<u8>> {
+            if !self.solved.get() {
+                return Err(WirehairError::NeedMore);
+            }
+
+            let mut message = vec![0u8; self.message_size as usize];
+            self.recover(&mut message)?;
+
+            Ok(message)
+        }
+
+        /// Same as `recover_vec`, but recovers into `buf`, resized to `message_size` within the
+        /// capacity it already has. Lets a receiver use one buffer for message after message.
+        pub fn recover_into_reusing(&self, buf: &mut Vec<u8>) -> Result<()> {
+            if !self.solved.get() {
+                return Err(WirehairError::NeedMore);
+            }
+
+            buf.resize(self.message_size as usize, 0);
+            self.recover(buf)?;
+
+            Ok(())
+        }
+
+        /// Decodes the given `(block_id, block)` pairs and returns the recovered message.
+        /// Fails with `ExtraInsufficient` if the blocks run out before the message can be solved,
+        /// or once far more blocks than N were consumed (see `recover_from_limited`).
+        pub fn recover_from<I, B>(
+            message_size_bytes: u64,
+            block_size_bytes: u32,
+            blocks: I,
+        ) -> Result<Vec<u8>>
+        where
+            I: IntoIterator<Item = (B, Vec<u8>)>,
+            B: Into<BlockId>,
+        {
+            let max_blocks = default_max_blocks(message_size_bytes, block_size_bytes);
+
+            WirehairDecoder::recover_from_limited(message_size_bytes, block_size_bytes, blocks, max_blocks)
+        }
+
+        /// Same as `recover_from`, but gives up with `ExtraInsufficient` after consuming
+        /// `max_blocks` blocks, so a sender which never supplies enough distinct blocks
+        /// can't keep the receiver busy forever
+        pub fn recover_from_limited<I, B>(
+            message_size_bytes: u64,
+            block_size_bytes: u32,
+            blocks: I,
+            max_blocks: u32,
+        ) -> Result<Vec<u8>>
+        where
+            I: IntoIterator<Item = (B, Vec<u8>)>,
+            B: Into<BlockId>,
+        {
+            let decoder = WirehairDecoder::new(&wirehair_init()?, message_size_bytes, block_size_bytes)?;
+
+            for (block_id, block) in blocks.into_iter().take(max_blocks as usize) {
+                if decoder.decode(block_id, &block, block.len() as u32)? == WirehairResult::Success {
+                    let mut message = vec![0u8; message_size_bytes as usize];
+                    decoder.recover(&mut message)?;
+
+                    return Ok(message);
+                }
+            }
+
+            Err(WirehairError::ExtraInsufficient)
+        }
+    }
+
+    /// A decoder which takes its sizing from the first packet it is fed, for receivers which
+    /// don't know what is coming. The native decoder is created on that first packet,
+    /// afterwards packets sized for another message are rejected with `InvalidInput`.
+    #[derive(Debug, Default)]
+    pub struct LazyDecoder {
+        decoder: Option<WirehairDecoder>,
+    }
+
+    impl LazyDecoder {
+        pub fn new() -> LazyDecoder {
+            LazyDecoder { decoder: None }
+        }
+
+        /// Decodes `packet` and returns whether the message can be recovered now.
+        /// A first packet with sizes the codec can't handle leaves the decoder uncreated.
+        pub fn feed(&mut self, packet: &Packet) -> Result<bool> {
+            let decoder = match &mut self.decoder {
+                Some(decoder) => decoder,
+                None => self.decoder.insert(WirehairDecoder::with_params(FecParams::new(
+                    packet.message_size,
+                    packet.block_size,
+                ))?),
+            };
+
+            Ok(decoder.decode_packet(packet)? == WirehairResult::Success)
+        }
+
+        /// The native decoder, `None` until the first packet has been fed
+        pub fn decoder(&self) -> Option<&WirehairDecoder> {
+            self.decoder.as_ref()
+        }
+
+        pub fn into_decoder(self) -> Option<WirehairDecoder> {
+            self.decoder
+        }
+
+        /// The whole message, `NeedMore` while it can't be recovered yet
+        pub fn recover_vec(&self) -> Result<Vec<u8>> {
+            self.decoder.as_ref().ok_or(WirehairError::NeedMore)?.recover_vec()
+        }
+    }
+
+    /// A decoder for a connection whose sizing was agreed on up front. Every packet is checked
+    /// against it before it gets near the codec, so call sites don't have to.
+    #[derive(Debug)]
+    pub struct Session {
+        params: FecParams,
+        decoder: WirehairDecoder,
+    }
+
+    impl Session {
+        pub fn new(params: FecParams) -> Result<Session> {
+            Ok(Session {
+                params,
+                decoder: WirehairDecoder::with_params(params)?,
+            })
+        }
+
+        pub fn params(&self) -> FecParams {
+            self.params
+        }
+
+        /// `InvalidInput` unless `packet` carries the agreed sizes and between 1 and
+        /// `block_size` bytes of data
+        pub fn check(&self, packet: &Packet) -> Result<()> {
+            let sized_right = packet.message_size == self.params.message_size
+                && packet.block_size == self.params.block_size;
+            let len_right =
+                !packet.data.is_empty() && packet.data.len() <= self.params.block_size as usize;
+
+            if !sized_right || !len_right {
+                return Err(WirehairError::InvalidInput);
+            }
+
+            Ok(())
+        }
+
+        /// Checks `packet`, decodes it and returns whether the message can be recovered now
+        pub fn feed(&self, packet: &Packet) -> Result<bool> {
+            self.check(packet)?;
+
+            self.decoder.feed(packet.block_id, &packet.data)
+        }
+
+        pub fn decoder(&self) -> &WirehairDecoder {
+            &self.decoder
+        }
+
+        pub fn into_decoder(self) -> WirehairDecoder {
+            self.decoder
+        }
+    }
+
+    // A codec handle waiting in a pool. SAFETY: same reasoning as `Send` for `WirehairEncoder`.
+    struct IdleHandle(NonNull<c_void>);
+
+    unsafe impl Send for IdleHandle {}
+
+    struct PoolInner {
+        max_idle: usize,
+        // Idle handles by the block size they were last used with
+        idle: Mutex<HashMap<u32, Vec<IdleHandle>>>,
+        reused: AtomicU64,
+    }
+
+    impl PoolInner {
+        fn take(&self, block_size_bytes: u32) -> *const c_void {
+            let handle = self
+                .idle
+                .lock()
+                .unwrap()
+                .get_mut(&block_size_bytes)
+                .and_then(|handles| handles.pop());
+
+            match handle {
+                Some(handle) => {
+                    self.reused.fetch_add(1, Ordering::Relaxed);
+                    handle.0.as_ptr()
+                }
+                None => null::<c_void>(),
+            }
+        }
+
+        // Returns false if the pool is full, the caller keeps the handle then
+        fn put(&self, block_size_bytes: u32, handle: NonNull<c_void>) -> bool {
+            let mut idle = self.idle.lock().unwrap();
+            if idle.values().map(Vec::len).sum::<usize>() >= self.max_idle {
+                return false;
+            }
+
+            idle.entry(block_size_bytes).or_default().push(IdleHandle(handle));
+            true
+        }
+    }
+
+    impl Drop for PoolInner {
+        fn drop(&mut self) {
+            let idle = self.idle.get_mut().unwrap_or_else(|e| e.into_inner());
+            for handle in idle.values().flatten() {
+                unsafe { wirehair_free(handle.0.as_ptr()) };
+            }
+        }
+    }
+
+    /// Recycles the native allocations of dropped codecs for new codecs with the same block size,
+    /// which saves the large mallocs of a server encoding many different messages.
+    /// At most `max_idle` handles are kept, the rest are freed as usual.
+    #[derive(Clone)]
+    pub struct CodecPool {
+        inner: Arc<PoolInner>,
+    }
+
+    impl CodecPool {
+        pub fn new(max_idle: usize) -> CodecPool {
+            CodecPool {
+                inner: Arc::new(PoolInner {
+                    max_idle,
+                    idle: Mutex::new(HashMap::new()),
+                    reused: AtomicU64::new(0),
+                }),
+            }
+        }
+
+        /// Same as `WirehairEncoder::from_slice`, on a recycled handle if there is one
+        pub fn encoder(
+            &self,
+            message: &[u8],
+            block_size_bytes: u32,
+        ) -> Result<PooledEncoder> {
+            let message_size_bytes =
+                u64::try_from(message.len()).map_err(|_| WirehairError::InvalidInput)?;
+            check_sizes(message_size_bytes, block_size_bytes)?;
+            wirehair_init()?;
+
+            // A failed creation frees the recycled handle along with it
+            let reuse_handler = self.inner.take(block_size_bytes);
+            let encoder = WirehairEncoder::create(reuse_handler, Arc::from(message), block_size_bytes)?;
+
+            Ok(PooledEncoder {
+                encoder: ManuallyDrop::new(encoder),
+                pool: self.clone(),
+            })
+        }
+
+        /// Same as `WirehairDecoder::new`, on a recycled handle if there is one
+        pub fn decoder(
+            &self,
+            message_size_bytes: u64,
+            block_size_bytes: u32,
+        ) -> Result<PooledDecoder> {
+            check_sizes(message_size_bytes, block_size_bytes)?;
+            wirehair_init()?;
+
+            let reuse_handler = self.inner.take(block_size_bytes);
+            let decoder = WirehairDecoder::create(reuse_handler, message_size_bytes, block_size_bytes)?;
+
+            Ok(PooledDecoder {
+                decoder: ManuallyDrop::new(decoder),
+                pool: self.clone(),
+            })
+        }
+
+        /// Number of handles waiting to be recycled
+        pub fn idle(&self) -> usize {
+            self.inner.idle.lock().unwrap().values().map(Vec::len).sum()
+        }
+
+        /// Number of codecs created on a recycled handle so far
+        pub fn reused(&self) -> u64 {
+            self.inner.reused.load(Ordering::Relaxed)
+        }
+    }
+
+    impl Debug for CodecPool {
+        fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+            f.debug_struct("CodecPool")
+                .field("max_idle", &self.inner.max_idle)
+                .field("idle", &self.idle())
+                .field("reused", &self.reused())
+                .finish()
+        }
+    }
+
+    /// An encoder from a `CodecPool`, its handle goes back to the pool on drop.
+    /// Derefs to `WirehairEncoder`.
+    #[derive(Debug)]
+    pub struct PooledEncoder {
+        encoder: ManuallyDrop<WirehairEncoder>,
+        pool: CodecPool,
+    }
+
+    impl Deref for PooledEncoder {
+        type Target = WirehairEncoder;
+
+        fn deref(&self) -> &WirehairEncoder {
+            &self.encoder
+        }
+    }
+
+    impl Drop for PooledEncoder {
+        fn drop(&mut self) {
+            let encoder = unsafe { ManuallyDrop::take(&mut self.encoder) };
+
+            if self.pool.inner.put(encoder.block_size, encoder.native_handler) {
+                encoder.into_handle();
+            }
+        }
+    }
+
+    /// A decoder from a `CodecPool`, its handle goes back to the pool on drop.
+    /// Derefs to `WirehairDecoder`.
+    #[derive(Debug)]
+    pub struct PooledDecoder {
+        decoder: ManuallyDrop<WirehairDecoder>,
+        pool: CodecPool,
+    }
+
+    impl Deref for PooledDecoder {
+        type Target = WirehairDecoder;
+
+        fn deref(&self) -> &WirehairDecoder {
+            &self.decoder
+        }
+    }
+
+    impl DerefMut for PooledDecoder {
+        fn deref_mut(&mut self) -> &mut WirehairDecoder {
+            &mut self.decoder
+        }
+    }
+
+    impl Drop for PooledDecoder {
+        fn drop(&mut self) {
+            let decoder = unsafe { ManuallyDrop::take(&mut self.decoder) };
+            let block_size = decoder.block_size;
+
+            let pooled = decoder
+                .native_handler
+                .is_some_and(|handle| self.pool.inner.put(block_size, handle));
+            if pooled {
+                decoder.into_handle();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::wirehair::*;
+    use std::convert::TryFrom;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    // Encodes `message`, skips every block for which `loss` returns true and decodes the rest
+    fn roundtrip(
+        message: &[u8],
+        block_size: u32,
+        loss: &mut impl FnMut(u64) -> bool,
+    ) -> Result<Vec<u8>> {
+        let encoder = WirehairEncoder::from_slice(message, block_size)?;
+        let decoder = WirehairDecoder::new(&wirehair_init()?, message.len() as u64, block_size)?;
+        let block_count = FecParams::new(message.len() as u64, block_size).validate()?;
+
+        // plenty for any loss pattern used below, but never loops forever
+        let max_blocks = block_count as usize * 10 + 100;
+
+        for block in encoder.blocks(0).take(max_blocks) {
+            let (block_id, block) = block?;
+            if loss(block_id.0) {
+                continue;
+            }
+
+            if decoder.decode(block_id, &block, block.len() as u32)? == WirehairResult::Success {
+                let mut recovered = vec![0u8; message.len()];
+                decoder.recover(&mut recovered)?;
+
+                return Ok(recovered);
+            }
+        }
+
+        Err(WirehairError::ExtraInsufficient)
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn random_messages_round_trip(
+            (message, block_size) in (2usize..100_000).prop_flat_map(|len| {
+                let min_block_size = (len as u32).div_ceil(MAX_BLOCK_COUNT);
+                (vec(any::<u8>(), len), min_block_size..len as u32)
+            }),
+            loss_seed in any::<u64>(),
+            loss_percent in 0u64..50,
+        ) {
+            assert!(wirehair_init().is_ok());
+
+            let mut loss = |block_id: u64| {
+                ((block_id ^ loss_seed).wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 32) % 100 < loss_percent
+            };
+
+            let recovered = roundtrip(&message, block_size, &mut loss);
+            prop_assert_eq!(recovered, Ok(message));
+        }
+    }
+
+    #[test]
+    fn basic_flow_works() {
+        let init = wirehair_init().unwrap();
+
+        let mut message = [0u8; 500];
+        for (i, byte) in message.iter_mut().enumerate() {
+            *byte = i as u8
+        }
+
+        let encoder = WirehairEncoder::new(&init, &message, 500, 50).unwrap();
+        let decoder = WirehairDecoder::new(&init, 500, 50).unwrap();
+
+        let mut block_id = 0;
+
+        loop {
+            let mut block = [0u8; 50];
+            let result = encoder.encode(block_id, &mut block);
+            assert!(result.is_ok());
+            let block_out_bytes = result.unwrap();
+
+            if block_id % 5 == 0 {
+                block_id += 1;
+                continue;
+            }
+
+            let result = decoder.decode(block_id, &block, block_out_bytes);
+            assert!(result.is_ok());
+
+            block_id += 1;
+
+            match result.unwrap() {
+                WirehairResult::NeedMore => continue,
+                WirehairResult::Success => break,
+            }
+        }
+
+        let mut decoded_message = [0u8; 500];
+
+        let result = decoder.recover(&mut decoded_message);
+        assert!(result.is_ok());
+
+        #[allow(deprecated)]
+        let converted = wirehair_decoder_to_encoder(decoder);
+        assert!(converted.is_ok());
+    }
+    #[test]
+    fn bad_parameters_return_error() {
+        let init = wirehair_init().unwrap();
+
+        let message = [0u8; 500];
+
+        // N = 1 is rejected by the library and used to leave a null handle behind
+        assert_eq!(
+            WirehairEncoder::new(&init, &message, 500, 500).err(),
+            Some(WirehairError::InvalidInput)
+        );
+        assert_eq!(
+            WirehairDecoder::new(&init, 500, 500).err(),
+            Some(WirehairError::InvalidInput)
+        );
+    }
+    #[test]
+    fn codecs_can_be_moved_to_another_thread() {
+        let init = wirehair_init().unwrap();
+
+        let message = [7u8; 500];
+
+        let encoder = WirehairEncoder::new(&init, &message, 500, 50).unwrap();
+        let decoder = WirehairDecoder::new(&init, 500, 50).unwrap();
+
+        let handle = std::thread::spawn(move || {
+            let mut block = [0u8; 50];
+            assert_eq!(encoder.encode(0, &mut block), Ok(50));
+
+            decoder.decode(0, &block, 50)
+        });
+
+        assert_eq!(handle.join().unwrap(), Ok(WirehairResult::NeedMore));
+    }
+    #[test]
+    fn block_iterator_works() {
+        let init = wirehair_init().unwrap();
+
+        let mut message = [0u8; 520];
+        for (i, byte) in message.iter_mut().enumerate() {
+            *byte = i as u8
+        }
+
+        let encoder = WirehairEncoder::new(&init, &message, 520, 50).unwrap();
+        let decoder = WirehairDecoder::new(&init, 520, 50).unwrap();
+
+        let mut solved = false;
+        for block in encoder.blocks(0).take(30) {
+            let (block_id, block) = block.unwrap();
+
+            // the last original block is truncated to the message tail
+            if block_id == BlockId(10) {
+                assert_eq!(block.len(), 20);
+                assert_eq!(&block[..], &message[500..]);
+            }
+
+            if block_id.0 % 3 == 0 {
+                continue;
+            }
+
+            if decoder.decode(block_id, &block, block.len() as u32) == Ok(WirehairResult::Success) {
+                solved = true;
+                break;
+            }
+        }
+        assert!(solved);
+
+        let mut decoded_message = [0u8; 520];
+        assert!(decoder.recover(&mut decoded_message).is_ok());
+        assert_eq!(&decoded_message[..], &message[..]);
+    }
+    #[test]
+    fn sizes_are_stored_in_codecs() {
+        let init = wirehair_init().unwrap();
+
+        let message = [0u8; 500];
+
+        let encoder = WirehairEncoder::new(&init, &message, 500, 50).unwrap();
+        assert_eq!(encoder.message_size(), 500);
+        assert_eq!(encoder.block_size(), 50);
+
+        let decoder = WirehairDecoder::new(&init, 500, 50).unwrap();
+        assert_eq!(decoder.message_size(), 500);
+        assert_eq!(decoder.block_size(), 50);
+    }
+    #[test]
+    fn concurrent_init_works() {
+        let barrier = std::sync::Arc::new(std::sync::Barrier::new(16));
+
+        let handles: Vec<_> = (0..16)
+            .map(|_| {
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    wirehair_init()
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            assert!(handle.join().unwrap().is_ok());
+        }
+        assert!(wirehair_init().is_ok());
+    }
+    #[test]
+    fn recover_from_works() {
+        let init = wirehair_init().unwrap();
+
+        let mut message = [0u8; 500];
+        for (i, byte) in message.iter_mut().enumerate() {
+            *byte = (i * 3) as u8
+        }
+
+        let encoder = WirehairEncoder::new(&init, &message, 500, 50).unwrap();
+        let blocks: Vec<(BlockId, Vec<u8>)> = encoder
+            .blocks(0)
+            .take(20)
+            .map(|block| block.unwrap())
+            .filter(|(block_id, _)| block_id.0 % 4 != 0)
+            .collect();
+
+        let recovered = WirehairDecoder::recover_from(500, 50, blocks.clone()).unwrap();
+        assert_eq!(&recovered[..], &message[..]);
+
+        let not_enough = blocks.into_iter().take(5);
+        assert_eq!(
+            WirehairDecoder::recover_from(500, 50, not_enough),
+            Err(WirehairError::ExtraInsufficient)
+        );
+    }
+    #[test]
+    fn endless_duplicates_are_given_up_on() {
+        let init = wirehair_init().unwrap();
+
+        let message = [7u8; 500];
+        let encoder = WirehairEncoder::new(&init, &message, 500, 50).unwrap();
+        let (block_id, block) = encoder.blocks(20).next().unwrap().unwrap();
+
+        // The same repair block forever never solves anything (duplicates of an original block
+        // are caught by the library itself)
+        let duplicates = std::iter::repeat((block_id, block.clone()));
+        assert_eq!(
+            WirehairDecoder::recover_from(500, 50, duplicates.clone()),
+            Err(WirehairError::ExtraInsufficient)
+        );
+        assert_eq!(
+            WirehairDecoder::recover_from_limited(500, 50, duplicates, 100),
+            Err(WirehairError::ExtraInsufficient)
+        );
+
+        let decoder = WirehairDecoder::new(&init, 500, 50).unwrap();
+        assert_eq!(
+            decoder.decode_many(std::iter::repeat((block_id, &block[..]))),
+            Err(WirehairError::ExtraInsufficient)
+        );
+        assert!(decoder.blocks_received() <= 10 + 10 / 2 + 32);
+    }
+    #[test]
+    fn codec_reuse_works() {
+        let init = wirehair_init().unwrap();
+
+        let first = [1u8; 500];
+        let second: Vec<u8> = (0..500).map(|i| i as u8).collect();
+
+        let encoder = WirehairEncoder::new(&init, &first, 500, 50).unwrap();
+        let encoder = WirehairEncoder::new_reusing(encoder, &second, 500, 50).unwrap();
+
+        let decoder = WirehairDecoder::new(&init, 500, 50).unwrap();
+        let decoder = WirehairDecoder::new_reusing(decoder, 500, 50).unwrap();
+
+        for block in encoder.blocks(0).take(10) {
+            let (block_id, block) = block.unwrap();
+            decoder.decode(block_id, &block, block.len() as u32).unwrap();
+        }
+
+        let mut decoded_message = [0u8; 500];
+        assert!(decoder.recover(&mut decoded_message).is_ok());
+        assert_eq!(&decoded_message[..], &second[..]);
+
+        // a failed creation consumes the reused codec without a double free
+        assert!(WirehairDecoder::new_reusing(decoder, 500, 500).is_err());
+    }
+
+    // cargo test --release -- --ignored --nocapture codec_reuse_timing
+    #[test]
+    #[ignore]
+    fn codec_reuse_timing() {
+        use std::time::Instant;
+
+        let init = wirehair_init().unwrap();
+
+        let message = vec![5u8; 1024 * 1024];
+        let size = message.len() as u64;
+
+        let start = Instant::now();
+        for _ in 0..20 {
+            WirehairEncoder::new(&init, &message, size, 1024).unwrap();
+        }
+        let fresh = start.elapsed();
+
+        let start = Instant::now();
+        let mut encoder = WirehairEncoder::new(&init, &message, size, 1024).unwrap();
+        for _ in 0..20 {
+            encoder = WirehairEncoder::new_reusing(encoder, &message, size, 1024).unwrap();
+        }
+        let reused = start.elapsed();
+
+        println!("20 fresh encoders: {:?}, 20 reused encoders: {:?}", fresh, reused);
+    }
+    #[test]
+    fn encoder_from_slice_works() {
+        let init = wirehair_init().unwrap();
+
+        let message = [3u8; 500];
+
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+        assert_eq!(encoder.message_size(), 500);
+
+        // the slice is shorter than the declared message size
+        assert_eq!(
+            WirehairEncoder::new(&init, &message[..400], 500, 50).err(),
+            Some(WirehairError::InvalidInput)
+        );
+    }
+    #[test]
+    fn fec_params_validation_works() {
+        assert_eq!(FecParams::new(500, 50).validate(), Ok(10));
+        assert_eq!(FecParams::new(501, 50).validate(), Ok(11));
+        assert_eq!(FecParams::new(0, 50).validate(), Err(WirehairError::InvalidInput));
+        assert_eq!(FecParams::new(500, 0).validate(), Err(WirehairError::InvalidInput));
+        assert_eq!(FecParams::new(50, 50).validate(), Err(WirehairError::BadInputSmallN));
+        assert_eq!(FecParams::new(64000, 1).validate(), Ok(64000));
+        assert_eq!(FecParams::new(64001, 1).validate(), Err(WirehairError::BadInputLargeN));
+        assert_eq!(
+            FecParams::new(u64::MAX, 1).validate(),
+            Err(WirehairError::BadInputLargeN)
+        );
+
+        for &message_size in &[2u64, 500, 1000, 1001, 1 << 20, 1 << 30] {
+            let block_size = FecParams::suggest_block_size(message_size);
+            assert!(FecParams::new(message_size, block_size).validate().is_ok());
+        }
+
+        assert!(wirehair_init().is_ok());
+
+        let message = [0u8; 500];
+        assert!(WirehairEncoder::with_params(&message, FecParams::new(500, 50)).is_ok());
+        assert_eq!(
+            WirehairDecoder::with_params(FecParams::new(500, 500)).err(),
+            Some(WirehairError::BadInputSmallN)
+        );
+    }
+    #[test]
+    fn blocks_received_counts_accepted_blocks() {
+        let init = wirehair_init().unwrap();
+
+        let message = [9u8; 500];
+
+        let encoder = WirehairEncoder::new(&init, &message, 500, 50).unwrap();
+        let decoder = WirehairDecoder::new(&init, 500, 50).unwrap();
+        assert_eq!(decoder.blocks_received(), 0);
+
+        // a block of the wrong size is rejected and not counted
+        assert!(decoder.decode(0, &[0u8; 50], 10).is_err());
+        assert_eq!(decoder.blocks_received(), 0);
+
+        for (i, block) in encoder.blocks(0).take(10).enumerate() {
+            let (block_id, block) = block.unwrap();
+            assert!(decoder.decode(block_id, &block, block.len() as u32).is_ok());
+            assert_eq!(decoder.blocks_received(), i as u32 + 1);
+        }
+    }
+    #[test]
+    fn encode_into_works() {
+        assert!(wirehair_init().is_ok());
+
+        let message: Vec<u8> = (0..520).map(|i| i as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+
+        let mut out = [0u8; 50];
+        assert_eq!(encoder.encode_into(0, &mut out), Ok(50));
+        assert_eq!(&out[..], &message[..50]);
+
+        assert_eq!(encoder.encode_into(10, &mut out), Ok(20));
+        assert_eq!(&out[..20], &message[500..]);
+
+        // the library refuses to write a block into a buffer which can't hold it
+        assert_eq!(
+            encoder.encode_into(0, &mut out[..10]),
+            Err(WirehairError::InvalidInput)
+        );
+    }
+    #[test]
+    fn blocks_for_loss_outnumber_the_losses() {
+        let message: Vec<u8> = (0..500).map(|i| (i * 23 + 9) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+        let decoder = WirehairDecoder::with_params(FecParams::new(500, 50)).unwrap();
+
+        let blocks: Vec<_> = encoder.blocks_for_loss(0.2).unwrap().map(Result::unwrap).collect();
+        assert!(blocks.len() >= 13, "{} blocks", blocks.len());
+        assert_eq!(blocks[0].0, BlockId(0));
+
+        // Every fifth block is lost
+        let mut solved = false;
+        for (index, (block_id, block)) in blocks.iter().enumerate() {
+            if index % 5 != 3 {
+                solved = decoder.feed(*block_id, block).unwrap();
+            }
+        }
+        assert!(solved);
+        assert_eq!(decoder.recover_vec(), Ok(message));
+
+        assert_eq!(encoder.blocks_for_loss(0.0).unwrap().count(), 12);
+        for loss_rate in [-0.1, 1.0, f64::NAN] {
+            assert!(encoder.blocks_for_loss(loss_rate).is_err());
+        }
+    }
+
+    #[test]
+    fn stats_count_codec_operations() {
+        let message: Vec<u8> = (0..520).map(|i| (i * 9 + 4) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+        let decoder = WirehairDecoder::with_params(FecParams::new(520, 50)).unwrap();
+
+        let mut block = [0u8; 50];
+        for block_id in 0..10u64 {
+            encoder.encode(block_id, &mut block).unwrap();
+        }
+        assert_eq!(encoder.stats().snapshot().encoded, 10);
+
+        let mut solved = false;
+        for block_id in 0..40u64 {
+            let len = encoder.encode_into(block_id, &mut block).unwrap();
+            solved = decoder.decode_dedup(block_id, &block[..len]).unwrap();
+            decoder.decode_dedup(block_id, &block[..len]).unwrap();
+            if solved {
+                break;
+            }
+        }
+        assert!(solved);
+        decoder.recover_vec().unwrap();
+
+        let stats = decoder.stats().snapshot();
+        assert_eq!(stats.encoded, 0);
+        assert_eq!(stats.decoded, u64::from(decoder.blocks_received()));
+        assert_eq!(stats.duplicates, stats.decoded);
+        assert_eq!(stats.recoveries, 1);
+    }
+
+    #[test]
+    fn sessions_reject_foreign_packets() {
+        let message: Vec<u8> = (0..520).map(|i| (i * 3 + 7) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+        let session = Session::new(FecParams::new(520, 50)).unwrap();
+        assert_eq!(session.params(), FecParams::new(520, 50));
+
+        let packet = encoder.encode_packet(0).unwrap();
+        let resized = Packet {
+            block_size: 40,
+            ..packet.clone()
+        };
+        assert_eq!(session.feed(&resized), Err(WirehairError::InvalidInput));
+        let oversized = Packet {
+            data: vec![0u8; 51],
+            ..packet.clone()
+        };
+        assert_eq!(session.feed(&oversized), Err(WirehairError::InvalidInput));
+        // Rejected before the codec saw them
+        assert_eq!(session.decoder().blocks_received(), 0);
+
+        let mut solved = session.feed(&packet).unwrap();
+        for block_id in 1..40 {
+            if solved {
+                break;
+            }
+            solved = session.feed(&encoder.encode_packet(block_id).unwrap()).unwrap();
+        }
+        assert!(solved);
+        assert_eq!(session.into_decoder().recover_vec(), Ok(message));
+    }
+
+    #[test]
+    fn short_blocks_leave_a_zeroed_tail() {
+        let message = vec![0xABu8; 520];
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+
+        // A buffer which held a whole block before, plus room past block_size
+        let mut out = [0xCDu8; 60];
+        assert_eq!(encoder.encode_into(3, &mut out), Ok(50));
+        assert_eq!(encoder.encode_into(10, &mut out), Ok(20));
+        assert_eq!(&out[..20], &message[500..]);
+        assert!(out[20..50].iter().all(|&byte| byte == 0));
+        // Bytes past block_size are none of the encoder's business
+        assert!(out[50..].iter().all(|&byte| byte == 0xCD));
+
+        let mut bufs = [[0xCDu8; 50]; 2];
+        let mut refs: Vec<&mut [u8]> = bufs.iter_mut().map(|buf| &mut buf[..]).collect();
+        assert_eq!(encoder.encode_batch(9, &mut refs), Ok(vec![50, 20]));
+        assert!(bufs[1][20..].iter().all(|&byte| byte == 0));
+    }
+    #[test]
+    fn packets_work() {
+        let init = wirehair_init().unwrap();
+
+        let message: Vec<u8> = (0..520).map(|i| (i * 5) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+        let decoder = WirehairDecoder::new(&init, 520, 50).unwrap();
+
+        let packet = encoder.encode_packet(10).unwrap();
+        assert_eq!(packet.data.len(), 20);
+        assert_eq!(packet.message_size, 520);
+
+        let foreign = Packet {
+            block_size: 40,
+            ..packet.clone()
+        };
+        assert_eq!(decoder.decode_packet(&foreign), Err(WirehairError::InvalidInput));
+
+        let mut result = decoder.decode_packet(&packet);
+        for block_id in 11..30 {
+            if result == Ok(WirehairResult::Success) {
+                break;
+            }
+            result = decoder.decode_packet(&encoder.encode_packet(block_id).unwrap());
+        }
+        assert_eq!(result, Ok(WirehairResult::Success));
+    }
+
+    #[test]
+    fn lazy_decoder_is_sized_by_the_first_packet() {
+        let message: Vec<u8> = (0..520).map(|i| (i * 13 + 2) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+
+        let mut decoder = LazyDecoder::new();
+        assert!(decoder.decoder().is_none());
+        assert_eq!(decoder.recover_vec(), Err(WirehairError::NeedMore));
+
+        // Sizes the codec can't handle leave nothing behind
+        let empty = Packet {
+            message_size: 0,
+            ..encoder.encode_packet(0).unwrap()
+        };
+        assert!(decoder.feed(&empty).is_err());
+        assert!(decoder.decoder().is_none());
+
+        assert_eq!(decoder.feed(&encoder.encode_packet(0).unwrap()), Ok(false));
+        assert_eq!(decoder.decoder().map(|decoder| decoder.message_size()), Some(520));
+
+        let foreign = Packet {
+            message_size: 600,
+            ..encoder.encode_packet(1).unwrap()
+        };
+        assert_eq!(decoder.feed(&foreign), Err(WirehairError::InvalidInput));
+
+        let mut solved = false;
+        for block_id in 2..40 {
+            solved = decoder.feed(&encoder.encode_packet(block_id).unwrap()).unwrap();
+            if solved {
+                break;
+            }
+        }
+        assert!(solved);
+        assert_eq!(decoder.recover_vec(), Ok(message));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn packets_survive_serialization() {
+        let init = wirehair_init().unwrap();
+
+        let message: Vec<u8> = (0..500).map(|i| (i * 5) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+        let decoder = WirehairDecoder::new(&init, 500, 50).unwrap();
+
+        for block_id in 0..10 {
+            let packet = encoder.encode_packet(block_id).unwrap();
+            let bytes = bincode::serialize(&packet).unwrap();
+            let received: Packet = bincode::deserialize(&bytes).unwrap();
+
+            assert_eq!(received, packet);
+            assert!(decoder.decode_packet(&received).is_ok());
+        }
+
+        let mut decoded_message = [0u8; 500];
+        assert!(decoder.recover(&mut decoded_message).is_ok());
+        assert_eq!(&decoded_message[..], &message[..]);
+    }
+    #[test]
+    fn codecs_implement_debug() {
+        let init = wirehair_init().unwrap();
+
+        let message = [0u8; 500];
+        let encoder = WirehairEncoder::new(&init, &message, 500, 50).unwrap();
+        let decoder = WirehairDecoder::new(&init, 500, 50).unwrap();
+
+        assert_eq!(
+            format!("{:?}", encoder),
+            "WirehairEncoder { message_size: 500, block_size: 50 }"
+        );
+        assert_eq!(
+            format!("{:?}", decoder),
+            "WirehairDecoder { message_size: 500, block_size: 50, blocks_received: 0 }"
+        );
+    }
+    #[test]
+    fn zero_sizes_are_rejected() {
+        let init = wirehair_init().unwrap();
+
+        let message = [0u8; 500];
+
+        assert_eq!(
+            WirehairEncoder::new(&init, &message, 500, 0).err(),
+            Some(WirehairError::InvalidInput)
+        );
+        assert_eq!(
+            WirehairEncoder::new(&init, &message, 0, 50).err(),
+            Some(WirehairError::InvalidInput)
+        );
+        assert_eq!(
+            WirehairDecoder::new(&init, 500, 0).err(),
+            Some(WirehairError::InvalidInput)
+        );
+        assert_eq!(
+            WirehairDecoder::new(&init, 0, 50).err(),
+            Some(WirehairError::InvalidInput)
+        );
+    }
+    #[test]
+    fn unknown_result_codes_are_errors() {
+        assert_eq!(parse_wirehair_result(0), Ok(WirehairResult::Success));
+        assert_eq!(parse_wirehair_result(1), Ok(WirehairResult::NeedMore));
+        assert_eq!(parse_wirehair_result(11), Err(WirehairError::Unknown(11)));
+        assert_eq!(
+            parse_wirehair_result(0x7fff_ffff),
+            Err(WirehairError::Unknown(0x7fff_ffff))
+        );
+        assert_eq!(parse_wirehair_result(-1), Err(WirehairError::Unknown(-1)));
+    }
+    #[test]
+    fn errors_convert_to_io_errors() {
+        use std::io;
+
+        let cases = [
+            (WirehairError::InvalidInput, io::ErrorKind::InvalidInput),
+            (WirehairError::BadDenseSeed, io::ErrorKind::Other),
+            (WirehairError::BadPeelSeed, io::ErrorKind::Other),
+            (WirehairError::BadInputSmallN, io::ErrorKind::Other),
+            (WirehairError::BadInputLargeN, io::ErrorKind::Other),
+            (WirehairError::ExtraInsufficient, io::ErrorKind::Other),
+            (WirehairError::Error, io::ErrorKind::Other),
+            (WirehairError::OOM, io::ErrorKind::OutOfMemory),
+            (WirehairError::UnsupportedPlatform, io::ErrorKind::Other),
+            (WirehairError::Io(io::ErrorKind::NotFound), io::ErrorKind::NotFound),
+            (WirehairError::Io(io::ErrorKind::PermissionDenied), io::ErrorKind::PermissionDenied),
+            (WirehairError::VersionMismatch, io::ErrorKind::Other),
+            (WirehairError::NeedMore, io::ErrorKind::Other),
+            (WirehairError::ChecksumMismatch, io::ErrorKind::InvalidData),
+            (WirehairError::Unknown(42), io::ErrorKind::Other),
+        ];
+
+        for (error, kind) in cases.iter() {
+            let io_error = io::Error::from(*error);
+
+            assert_eq!(io_error.kind(), *kind);
+            assert_eq!(io_error.to_string(), error.to_string());
+        }
+    }
+    #[test]
+    fn version_handshake_works() {
+        assert_eq!(wirehair_version(), WIREHAIR_VERSION);
+        assert!(wirehair_init().is_ok());
+
+        assert_eq!(parse_init_result(0), Ok(()));
+        assert_eq!(parse_init_result(2), Err(WirehairError::VersionMismatch));
+        assert_eq!(parse_init_result(10), Err(WirehairError::UnsupportedPlatform));
+    }
+
+    #[test]
+    fn platform_is_supported() {
+        assert!(is_supported());
+        assert!(wirehair_init().is_ok());
+    }
+    #[test]
+    fn decoder_reset_works() {
+        let init = wirehair_init().unwrap();
+
+        let first: Vec<u8> = (0..500).map(|i| i as u8).collect();
+        let second: Vec<u8> = (0..500).map(|i| (i * 7 + 3) as u8).collect();
+
+        let mut decoder = WirehairDecoder::new(&init, 500, 50).unwrap();
+
+        for message in [&first, &second].iter() {
+            let encoder = WirehairEncoder::from_slice(message, 50).unwrap();
+
+            let mut result = Ok(WirehairResult::NeedMore);
+            for block in encoder.blocks(0).take(20) {
+                let (block_id, block) = block.unwrap();
+                result = decoder.decode(block_id, &block, block.len() as u32);
+                if result == Ok(WirehairResult::Success) {
+                    break;
+                }
+            }
+            assert_eq!(result, Ok(WirehairResult::Success));
+
+            let mut decoded_message = [0u8; 500];
+            assert!(decoder.recover(&mut decoded_message).is_ok());
+            assert_eq!(&decoded_message[..], &message[..]);
+
+            assert!(decoder.reset().is_ok());
+            assert_eq!(decoder.blocks_received(), 0);
+            assert_eq!(decoder.message_size(), 500);
+        }
+    }
+    #[test]
+    fn oversized_blocks_are_rejected() {
+        let init = wirehair_init().unwrap();
+
+        let decoder = WirehairDecoder::new(&init, 500, 50).unwrap();
+
+        // the declared length is larger than the buffer
+        assert_eq!(
+            decoder.decode(0, &[0u8; 10], 50),
+            Err(WirehairError::InvalidInput)
+        );
+        // the declared length is larger than a block
+        assert_eq!(
+            decoder.decode(0, &[0u8; 100], 100),
+            Err(WirehairError::InvalidInput)
+        );
+        // the library would take this id for block 0
+        assert_eq!(
+            decoder.decode(1u64 << 32, &[0u8; 50], 50),
+            Err(WirehairError::InvalidInput)
+        );
+        assert_eq!(decoder.blocks_received(), 0);
+
+        assert_eq!(decoder.decode(0, &[0u8; 100], 50), Ok(WirehairResult::NeedMore));
+    }
+    #[test]
+    fn encode_all_works() {
+        assert!(wirehair_init().is_ok());
+
+        let message: Vec<u8> = (0..520).map(|i| i as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+
+        let blocks = encoder.encode_all(15).unwrap();
+        assert_eq!(blocks.len(), 15);
+
+        for (i, (block_id, block)) in blocks.iter().enumerate() {
+            assert_eq!(*block_id, BlockId(i as u64));
+            assert!(!block.is_empty());
+        }
+
+        let encoded_size: usize = blocks[..11].iter().map(|(_, block)| block.len()).sum();
+        assert_eq!(encoded_size, message.len());
+    }
+    #[test]
+    fn errors_are_copy() {
+        fn assert_copy<T: Copy + Eq>() {}
+        assert_copy::<WirehairError>();
+
+        let error = WirehairError::Unknown(42);
+        let copy = error;
+        assert_eq!(error, copy);
+        assert_eq!(std::mem::size_of::<WirehairError>(), 8);
+    }
+    #[test]
+    fn results_compare_directly() {
+        let init = wirehair_init().unwrap();
+
+        let message = [4u8; 500];
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+        let decoder = WirehairDecoder::new(&init, 500, 50).unwrap();
+
+        let (block_id, block) = encoder.blocks(0).next().unwrap().unwrap();
+        let result = decoder.decode(block_id, &block, block.len() as u32).unwrap();
+        assert_eq!(result, WirehairResult::NeedMore);
+        assert_ne!(result, WirehairResult::Success);
+
+        let copy = result;
+        assert_eq!(copy, result);
+    }
+    #[test]
+    fn decoder_codec_becomes_encoder() {
+        let init = wirehair_init().unwrap();
+
+        let message: Vec<u8> = (0..500).map(|i| (i * 11) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+        let decoder = WirehairDecoder::new(&init, 500, 50).unwrap();
+
+        for block in encoder.blocks(0).take(12) {
+            let (block_id, block) = block.unwrap();
+            if decoder.decode(block_id, &block, block.len() as u32) == Ok(WirehairResult::Success) {
+                break;
+            }
+        }
+
+        let codec = Codec::Decoder(decoder).into_encoder().unwrap();
+        let codec = codec.into_encoder().unwrap();
+
+        match codec {
+            Codec::Encoder(encoder) => {
+                let mut out = [0u8; 50];
+                assert_eq!(encoder.encode_into(3, &mut out), Ok(50));
+                assert_eq!(&out[..], &message[150..200]);
+            }
+            Codec::Decoder(_) => panic!("expected an encoder"),
+        }
+    }
+    #[test]
+    fn converted_decoder_is_freed_once() {
+        let init = wirehair_init().unwrap();
+
+        let message: Vec<u8> = (0..500).map(|i| (i * 13) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+
+        for _ in 0..10 {
+            let decoder = WirehairDecoder::new(&init, 500, 50).unwrap();
+            for block in encoder.blocks(0).take(12) {
+                let (block_id, block) = block.unwrap();
+                if decoder.decode(block_id, &block, block.len() as u32) == Ok(WirehairResult::Success) {
+                    break;
+                }
+            }
+
+            // used to free the handle twice, once here and once when the encoder was dropped
+            let converted = WirehairEncoder::try_from(decoder).unwrap();
+
+            let mut out = [0u8; 50];
+            let mut expected = [0u8; 50];
+            assert_eq!(converted.encode_into(20, &mut out), Ok(50));
+            assert_eq!(encoder.encode_into(20, &mut expected), Ok(50));
+            assert_eq!(out, expected);
+        }
+    }
+    #[test]
+    fn cloned_encoders_are_independent() {
+        assert!(wirehair_init().is_ok());
+
+        let message: Vec<u8> = (0..500).map(|i| (i * 17) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+        let clone = encoder.clone();
+        drop(encoder);
+
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let encoder = clone.clone();
+                std::thread::spawn(move || encoder.encode_packet(42).unwrap())
+            })
+            .collect();
+
+        let packets: Vec<Packet> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(packets[0], packets[1]);
+        assert_eq!(packets[0], clone.encode_packet(42).unwrap());
+    }
+    #[test]
+    fn encode_range_works() {
+        assert!(wirehair_init().is_ok());
+
+        let message: Vec<u8> = (0..500).map(|i| (i * 19) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+
+        let ids: Vec<u64> = encoder
+            .encode_range(100..150)
+            .map(|block| block.unwrap().0.into())
+            .collect();
+        assert_eq!(ids, (100..150).collect::<Vec<u64>>());
+
+        let first = encoder.encode_range(120..121).next().unwrap().unwrap();
+        let second = encoder.encode_range(120..121).next().unwrap().unwrap();
+        assert_eq!(first, second);
+
+        #[allow(clippy::reversed_empty_ranges)]
+        let reversed = 150..100;
+        assert_eq!(encoder.encode_range(reversed).count(), 0);
+    }
+    #[test]
+    fn usize_sizes_work() {
+        assert!(wirehair_init().is_ok());
+
+        let message = [6u8; 500];
+        let encoder = WirehairEncoder::new_usize(&message, message.len(), 50).unwrap();
+        assert_eq!(encoder.message_size(), 500);
+
+        let decoder = WirehairDecoder::new_usize(message.len(), 50).unwrap();
+        assert_eq!(decoder.block_size(), 50);
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn oversized_usize_block_size_is_rejected() {
+        assert!(wirehair_init().is_ok());
+
+        let message = [6u8; 500];
+        let block_size = u32::MAX as usize + 1;
+
+        assert_eq!(
+            WirehairEncoder::new_usize(&message, 500, block_size).err(),
+            Some(WirehairError::InvalidInput)
+        );
+        assert_eq!(
+            WirehairDecoder::new_usize(500, block_size).err(),
+            Some(WirehairError::InvalidInput)
+        );
+    }
+
+    #[cfg(target_pointer_width = "32")]
+    #[test]
+    fn unaddressable_message_size_is_rejected() {
+        let init = wirehair_init().unwrap();
+
+        // a recovered message of this size couldn't even be allocated
+        assert_eq!(
+            WirehairDecoder::new(&init, u64::from(u32::MAX) + 1, 1 << 20).err(),
+            Some(WirehairError::InvalidInput)
+        );
+    }
+    #[test]
+    fn recover_vec_works() {
+        let init = wirehair_init().unwrap();
+
+        let message: Vec<u8> = (0..520).map(|i| (i * 23) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+        let decoder = WirehairDecoder::new(&init, 520, 50).unwrap();
+
+        assert_eq!(decoder.recover_vec(), Err(WirehairError::NeedMore));
+
+        for block in encoder.blocks(0).take(15) {
+            let (block_id, block) = block.unwrap();
+            if decoder.decode(block_id, &block, block.len() as u32) == Ok(WirehairResult::Success) {
+                break;
+            }
+        }
+
+        assert_eq!(decoder.recover_vec(), Ok(message));
+
+        // a buffer shorter than the message is refused instead of overflowed
+        assert_eq!(
+            decoder.recover(&mut [0u8; 519]),
+            Err(WirehairError::InvalidInput)
+        );
+    }
+    #[test]
+    fn overhead_is_reported_once_solved() {
+        let init = wirehair_init().unwrap();
+
+        let message: Vec<u8> = (0..50_000).map(|i| (i * 29) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+        let decoder = WirehairDecoder::new(&init, 50_000, 50).unwrap();
+
+        for block in encoder.blocks(0).take(1100) {
+            let (block_id, block) = block.unwrap();
+            assert_eq!(decoder.overhead(), None);
+
+            if decoder.decode(block_id, &block, block.len() as u32) == Ok(WirehairResult::Success) {
+                break;
+            }
+        }
+
+        let overhead = decoder.overhead().unwrap();
+        assert!((1.0..1.01).contains(&overhead), "overhead {}", overhead);
+    }
+
+    #[test]
+    fn remaining_blocks_are_estimated() {
+        let init = wirehair_init().unwrap();
+
+        let message: Vec<u8> = (0..1000).map(|i| (i * 31) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+        let decoder = WirehairDecoder::new(&init, 1000, 50).unwrap();
+        assert_eq!(decoder.blocks_remaining_estimate(), Some(20));
+
+        for block in encoder.blocks(0).take(17) {
+            let (block_id, block) = block.unwrap();
+            decoder.decode(block_id, &block, block.len() as u32).unwrap();
+        }
+        assert_eq!(decoder.recover_vec(), Err(WirehairError::NeedMore));
+        assert_eq!(decoder.blocks_remaining_estimate(), Some(3));
+
+        for block in encoder.blocks(17).take(10) {
+            let (block_id, block) = block.unwrap();
+            if decoder.feed(block_id, &block).unwrap() {
+                break;
+            }
+        }
+        assert_eq!(decoder.blocks_remaining_estimate(), None);
+    }
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_encode_still_works() {
+        assert!(wirehair_init().is_ok());
+
+        let message: Vec<u8> = (0..520).map(|i| i as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+
+        let mut block = [0u8; 50];
+        let mut block_out_bytes: u32 = 0;
+        let result = encoder.encode_out_bytes(10, &mut block, &mut block_out_bytes);
+
+        assert_eq!(result, Ok(WirehairResult::Success));
+        assert_eq!(block_out_bytes, 20);
+        assert_eq!(encoder.encode(10, &mut block), Ok(20));
+    }
+    #[test]
+    fn block_ids_round_trip_through_u64() {
+        let id = BlockId::from(42u64);
+        assert_eq!(id, BlockId(42));
+        assert_eq!(u64::from(id), 42);
+
+        let raw: u64 = BlockId(7).into();
+        assert_eq!(raw, 7);
+
+        assert!(wirehair_init().is_ok());
+
+        let message: Vec<u8> = (0..500).map(|i| i as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+
+        let mut by_id = [0u8; 50];
+        let mut by_raw = [0u8; 50];
+        assert_eq!(encoder.encode(BlockId(3), &mut by_id), Ok(50));
+        assert_eq!(encoder.encode(3, &mut by_raw), Ok(50));
+        assert_eq!(by_id, by_raw);
+    }
+    #[test]
+    fn decode_many_stops_once_solved() {
+        let init = wirehair_init().unwrap();
+
+        let message: Vec<u8> = (0..500).map(|i| (i * 31) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+        let blocks = encoder.encode_all(30).unwrap();
+
+        let decoder = WirehairDecoder::new(&init, 500, 50).unwrap();
+        let burst = blocks.iter().map(|(block_id, block)| (*block_id, &block[..]));
+
+        assert_eq!(decoder.decode_many(burst.clone().take(5)), Ok(false));
+        assert_eq!(decoder.decode_many(burst.skip(5)), Ok(true));
+
+        // the blocks after the solving one are not fed to the library
+        assert!(decoder.blocks_received() < 30);
+        assert_eq!(decoder.recover_vec(), Ok(message));
+    }
+    #[test]
+    fn block_count_rounds_up() {
+        assert!(wirehair_init().is_ok());
+
+        let message = [8u8; 64_000];
+
+        let exact = WirehairEncoder::from_slice(&message[..500], 50).unwrap();
+        assert_eq!(exact.block_count(), 10);
+
+        let partial = WirehairEncoder::from_slice(&message[..501], 50).unwrap();
+        assert_eq!(partial.block_count(), 11);
+
+        let largest = WirehairEncoder::from_slice(&message, 1).unwrap();
+        assert_eq!(largest.block_count(), MAX_BLOCK_COUNT);
+
+        // the last original block holds the single leftover byte
+        let last_id = u64::from(partial.block_count() - 1);
+        assert_eq!(partial.encode(last_id, &mut [0u8; 50]), Ok(1));
+    }
+    #[test]
+    fn try_recover_reports_need_more() {
+        let init = wirehair_init().unwrap();
+
+        let message: Vec<u8> = (0..500).map(|i| (i * 41) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+        let decoder = WirehairDecoder::new(&init, 500, 50).unwrap();
+
+        let mut decoded_message = [0u8; 500];
+        assert_eq!(decoder.try_recover(&mut decoded_message), Ok(None));
+
+        let blocks = encoder.encode_all(5).unwrap();
+        decoder.decode_many(blocks.iter().map(|(id, block)| (*id, &block[..]))).unwrap();
+        assert_eq!(decoder.try_recover(&mut decoded_message), Ok(None));
+
+        let blocks = encoder.encode_range(5..20).map(|block| block.unwrap()).collect::<Vec<_>>();
+        decoder.decode_many(blocks.iter().map(|(id, block)| (*id, &block[..]))).unwrap();
+        assert_eq!(decoder.try_recover(&mut decoded_message), Ok(Some(())));
+        assert_eq!(&decoded_message[..], &message[..]);
+
+        assert_eq!(
+            decoder.try_recover(&mut decoded_message[..10]),
+            Err(WirehairError::InvalidInput)
+        );
+    }
+    #[test]
+    fn decoder_size_can_be_limited() {
+        assert!(wirehair_init().is_ok());
+
+        assert_eq!(
+            WirehairDecoder::new_limited(1001, 10, 100).err(),
+            Some(WirehairError::BadInputLargeN)
+        );
+
+        let decoder = WirehairDecoder::new_limited(1000, 10, 100).unwrap();
+        assert_eq!(decoder.message_size(), 1000);
+
+        // the library's own limits still apply
+        assert_eq!(
+            WirehairDecoder::new_limited(10, 10, 100).err(),
+            Some(WirehairError::BadInputSmallN)
+        );
+    }
+    #[test]
+    fn owned_encoder_outlives_the_original_data() {
+        assert!(wirehair_init().is_ok());
+
+        let message: Vec<u8> = (0..520).map(|i| (i * 43) as u8).collect();
+        let expected = message.clone();
+
+        let encoder = OwnedEncoder::new(message, 50).unwrap();
+        assert_eq!(encoder.message(), &expected[..]);
+
+        let blocks = encoder.encode_all(20).unwrap();
+        drop(encoder);
+
+        let recovered = WirehairDecoder::recover_from(520, 50, blocks.into_iter().skip(3));
+        assert_eq!(recovered, Ok(expected));
+
+        assert_eq!(
+            OwnedEncoder::new(Vec::new(), 50).err(),
+            Some(WirehairError::InvalidInput)
+        );
+    }
+    #[test]
+    fn message_may_be_dropped_after_new() {
+        assert!(wirehair_init().is_ok());
+
+        let expected: Vec<u8> = (0..520).map(|i| (i * 47) as u8).collect();
+
+        let mut message = expected.clone();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+
+        // the encoder must not see writes to or the release of the caller's buffer
+        message.iter_mut().for_each(|byte| *byte = 0);
+        drop(message);
+        let _reuse: Vec<u8> = vec![0xAA; 520];
+
+        let blocks = encoder.encode_all(15).unwrap();
+        assert_eq!(&blocks[0].1[..], &expected[..50]);
+        assert_eq!(WirehairDecoder::recover_from(520, 50, blocks), Ok(expected));
+    }
+    #[test]
+    fn encoder_builder_collects_writes() {
+        use std::io::Write;
+
+        assert!(wirehair_init().is_ok());
+
+        let message: Vec<u8> = (0..520).map(|i| (i * 53) as u8).collect();
+
+        let mut builder = EncoderBuilder::new();
+        for chunk in message.chunks(77) {
+            builder.write_all(chunk).unwrap();
+        }
+        write!(builder, "tail").unwrap();
+        builder.flush().unwrap();
+
+        let encoder = builder.finish(50).unwrap();
+        assert_eq!(encoder.message_size(), 524);
+
+        let mut expected = message;
+        expected.extend_from_slice(b"tail");
+
+        let blocks = encoder.blocks(0).take(20).map(|block| block.unwrap()).skip(2);
+        assert_eq!(WirehairDecoder::recover_from(524, 50, blocks), Ok(expected));
+
+        assert_eq!(
+            EncoderBuilder::new().finish(50).err(),
+            Some(WirehairError::InvalidInput)
+        );
+    }
+    #[test]
+    fn feed_reports_completion() {
+        let init = wirehair_init().unwrap();
+
+        let message: Vec<u8> = (0..520).map(|i| (i * 59) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+        let decoder = WirehairDecoder::new(&init, 520, 50).unwrap();
+
+        assert_eq!(decoder.feed(40, &[]), Err(WirehairError::InvalidInput));
+
+        // the first original blocks never arrive
+        let mut blocks = encoder.blocks(3);
+        loop {
+            let (block_id, block) = blocks.next().unwrap().unwrap();
+            if decoder.feed(block_id, &block).unwrap() {
+                break;
+            }
+        }
+
+        assert_eq!(decoder.recover_vec(), Ok(message));
+    }
+    #[test]
+    fn scheduled_blocks_are_reproducible() {
+        assert!(wirehair_init().is_ok());
+
+        let message: Vec<u8> = (0..520).map(|i| (i * 61) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+
+        let ids = |seed| -> Vec<BlockId> {
+            encoder
+                .scheduled_blocks(seed, 40)
+                .map(|block| block.unwrap().0)
+                .collect()
+        };
+
+        let first = ids(7);
+        assert_eq!(first, ids(7));
+        assert_ne!(first, ids(8));
+        assert_ne!(first, (0..40).map(BlockId).collect::<Vec<_>>());
+
+        let mut sorted: Vec<u64> = first.iter().map(|id| id.0).collect();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..40).collect::<Vec<u64>>());
+
+        // only the first half of the schedule is delivered
+        let blocks = encoder.scheduled_blocks(7, 40).take(20).map(|block| block.unwrap());
+        assert_eq!(WirehairDecoder::recover_from(520, 50, blocks), Ok(message));
+    }
+    #[test]
+    fn solved_decoder_ignores_further_blocks() {
+        let init = wirehair_init().unwrap();
+
+        let message: Vec<u8> = (0..500).map(|i| (i * 67) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+        let decoder = WirehairDecoder::new(&init, 500, 50).unwrap();
+
+        let mut blocks = encoder.blocks(0).map(|block| block.unwrap());
+        for (block_id, block) in blocks.by_ref() {
+            if decoder.feed(block_id, &block).unwrap() {
+                break;
+            }
+        }
+        let received = decoder.blocks_received();
+
+        for (block_id, block) in blocks.take(5) {
+            assert_eq!(
+                decoder.decode(block_id, &block, block.len() as u32),
+                Ok(WirehairResult::Success)
+            );
+        }
+        // even garbage is ignored now
+        assert_eq!(decoder.decode(0, &[0xFF; 50], 50), Ok(WirehairResult::Success));
+
+        assert_eq!(decoder.blocks_received(), received);
+        assert_eq!(decoder.recover_vec(), Ok(message));
+    }
+    #[test]
+    fn block_size_fits_mtu() {
+        assert_eq!(FecParams::block_size_for_mtu(10 * 1024, 1200), Ok(1200));
+        assert_eq!(FecParams::block_size_for_mtu(500, 1200), Ok(499));
+        assert_eq!(FecParams::block_size_for_mtu(64_000 * 1200, 1200), Ok(1200));
+
+        // more than 64000 blocks of 1200 bytes
+        assert_eq!(
+            FecParams::block_size_for_mtu(64_000 * 1200 + 1, 1200),
+            Err(WirehairError::BadInputLargeN)
+        );
+        assert_eq!(
+            FecParams::block_size_for_mtu(u64::MAX, u32::MAX),
+            Err(WirehairError::BadInputLargeN)
+        );
+        assert_eq!(
+            FecParams::block_size_for_mtu(1, 1200),
+            Err(WirehairError::BadInputSmallN)
+        );
+        assert_eq!(
+            FecParams::block_size_for_mtu(500, 0),
+            Err(WirehairError::InvalidInput)
+        );
+
+        for &(message_size, mtu) in &[(10u64 * 1024, 1200u32), (2, 1200), (1 << 30, 65_507)] {
+            let block_size = FecParams::block_size_for_mtu(message_size, mtu).unwrap();
+            assert!(block_size <= mtu);
+            assert!(FecParams::new(message_size, block_size).validate().is_ok());
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "system-wirehair"))]
+    fn simd_path_is_detected() {
+        assert!(wirehair_init().is_ok());
+
+        let level = active_simd();
+        if cfg!(target_arch = "x86_64") {
+            // Every x86_64 CI runner has had SSSE3 for well over a decade
+            assert!(level >= SimdLevel::Ssse3, "{:?}", level);
+        }
+        if cfg!(target_arch = "aarch64") {
+            assert_eq!(level, SimdLevel::Neon);
+        }
+        assert_eq!(active_simd(), level);
+    }
+
+    #[test]
+    fn failed_creation_is_an_error() {
+        let init = wirehair_init().unwrap();
+
+        // N = 64001 passes the zero size checks but is refused by the library itself
+        let message = vec![0u8; 64_001];
+        assert_eq!(
+            WirehairEncoder::new(&init, &message, 64_001, 1).err(),
+            Some(WirehairError::InvalidInput)
+        );
+        assert_eq!(WirehairDecoder::new(&init, 64_001, 1).err(), Some(WirehairError::InvalidInput));
+
+        // The reused codec is freed by the library on failure and must not be freed again
+        let old = WirehairDecoder::new(&init, 500, 50).unwrap();
+        assert_eq!(
+            WirehairDecoder::new_reusing(old, 64_001, 1).err(),
+            Some(WirehairError::InvalidInput)
+        );
+
+        // A handle can't be null, which leaves a niche for `Option`
+        assert_eq!(
+            std::mem::size_of::<Option<WirehairEncoder>>(),
+            std::mem::size_of::<WirehairEncoder>()
+        );
+    }
+
+    #[test]
+    fn pooled_codecs_reuse_handles() {
+        assert!(wirehair_init().is_ok());
+
+        let pool = CodecPool::new(2);
+        for i in 0..10u32 {
+            let message: Vec<u8> = (0..1000 + i * 10).map(|j| (j * 7 + i) as u8).collect();
+            let encoder = pool.encoder(&message, 50).unwrap();
+            let decoder = pool.decoder(message.len() as u64, 50).unwrap();
+
+            for block in encoder.blocks(0).take(100) {
+                let (block_id, block) = block.unwrap();
+                if decoder.feed(block_id, &block).unwrap() {
+                    break;
+                }
+            }
+            assert_eq!(decoder.recover_vec(), Ok(message));
+        }
+
+        // Every round after the first one runs on the two handles of the round before
+        assert_eq!(pool.reused(), 18);
+        assert_eq!(pool.idle(), 2);
+
+        // Handles are only recycled for the block size they were used with, and never beyond the bound
+        let other = pool.encoder(&[1u8; 1000], 100).unwrap();
+        assert_eq!(pool.reused(), 18);
+        let decoder = pool.decoder(1000, 50).unwrap();
+        assert_eq!(pool.idle(), 1);
+        drop(other);
+        drop(decoder);
+        assert_eq!(pool.idle(), 2);
+    }
+
+    #[test]
+    fn decoder_converts_into_encoder() -> Result<()> {
+        let init = wirehair_init().unwrap();
+
+        let message: Vec<u8> = (0..520).map(|i| (i * 11) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50)?;
+        let decoder = WirehairDecoder::new(&init, 520, 50)?;
+        for block in encoder.blocks(0).take(20) {
+            let (block_id, block) = block?;
+            if decoder.feed(block_id, &block)? {
+                break;
+            }
+        }
+
+        let relay = WirehairEncoder::try_from(decoder)?;
+        let mut block = [0u8; 50];
+        let mut expected = [0u8; 50];
+        assert_eq!(relay.encode(30, &mut block)?, encoder.encode(30, &mut expected)?);
+        assert_eq!(block, expected);
+
+        // An unsolved decoder has nothing to encode
+        let unsolved = WirehairDecoder::new(&init, 520, 50)?;
+        assert_eq!(
+            WirehairEncoder::try_from(unsolved).err(),
+            Some(WirehairError::NeedMore)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn decode_progress_is_reported() {
+        let init = wirehair_init().unwrap();
+
+        let message: Vec<u8> = (0..500).map(|i| (i * 3 + 1) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+        let decoder = WirehairDecoder::new(&init, 500, 50).unwrap();
+
+        let mut blocks = encoder.blocks(0).map(|block| block.unwrap());
+        for received in 1..10 {
+            let (block_id, block) = blocks.next().unwrap();
+            let progress = decoder.decode_progress(block_id, &block).unwrap();
+            assert_eq!(
+                progress,
+                DecodeProgress { complete: false, blocks_received: received, blocks_needed: 10 - received }
+            );
+        }
+
+        // Ten original blocks are all it takes
+        let (block_id, block) = blocks.next().unwrap();
+        let progress = decoder.decode_progress(block_id, &block).unwrap();
+        assert_eq!(
+            progress,
+            DecodeProgress { complete: true, blocks_received: 10, blocks_needed: 0 }
+        );
+    }
+
+    #[test]
+    fn original_blocks_are_readable_early() {
+        let init = wirehair_init().unwrap();
+
+        let message: Vec<u8> = (0..520).map(|i| (i * 17 + 5) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+        let mut decoder = WirehairDecoder::new(&init, 520, 50).unwrap();
+        decoder.retain_original_blocks();
+
+        let (block_id, block) = encoder.blocks(3).next().unwrap().unwrap();
+        assert!(!decoder.feed(block_id, &block).unwrap());
+        assert_eq!(decoder.recovered_block(3), Some(message[150..200].to_vec()));
+        assert_eq!(decoder.recovered_block(4), None);
+
+        for block in encoder.blocks(20).take(20) {
+            let (block_id, block) = block.unwrap();
+            if decoder.feed(block_id, &block).unwrap() {
+                break;
+            }
+        }
+
+        // Once solved every original block is there, the last one with its valid bytes only
+        assert_eq!(decoder.recovered_block(4), Some(message[200..250].to_vec()));
+        assert_eq!(decoder.recovered_block(10), Some(message[500..].to_vec()));
+        assert_eq!(decoder.recovered_block(11), None);
+
+        // Without retention nothing is kept
+        let plain = WirehairDecoder::new(&init, 520, 50).unwrap();
+        plain.feed(block_id, &block).unwrap();
+        assert_eq!(plain.recovered_block(3), None);
+    }
+
+    #[test]
+    fn misaligned_buffers_work() {
+        let init = wirehair_init().unwrap();
+
+        // Every caller buffer starts at an odd address. The encoder copies the message,
+        // but the blocks and the recovery target are handed to the library as they are.
+        let mut storage: Vec<u8> = (0..1001).map(|i| (i * 7 + i / 13) as u8).collect();
+        let offset = 1 - storage.as_ptr() as usize % 2;
+        let message = storage[offset..offset + 1000].to_vec();
+
+        let encoder = WirehairEncoder::from_slice(&storage[offset..offset + 1000], 64).unwrap();
+        let decoder = WirehairDecoder::new(&init, 1000, 64).unwrap();
+        let mut block = [0u8; 65];
+        for block_id in (0..40u64).filter(|block_id| block_id % 3 != 0) {
+            let len = encoder.encode(block_id, &mut block[1..]).unwrap() as usize;
+            if decoder.feed(block_id, &block[1..1 + len]).unwrap() {
+                break;
+            }
+        }
+
+        decoder.recover(&mut storage[offset..offset + 1000]).unwrap();
+        assert_eq!(&storage[offset..offset + 1000], &message[..]);
+    }
+
+    #[test]
+    fn recover_buffer_is_reused() {
+        let init = wirehair_init().unwrap();
+
+        let mut decoder = WirehairDecoder::new(&init, 520, 50).unwrap();
+        let mut buf = Vec::with_capacity(4096);
+        assert_eq!(decoder.recover_into_reusing(&mut buf), Err(WirehairError::NeedMore));
+
+        for seed in 0..2u32 {
+            let message: Vec<u8> = (0..520).map(|i| (i * 7 + seed) as u8).collect();
+            let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+            for block in encoder.blocks(u64::from(seed)).take(20) {
+                let (block_id, block) = block.unwrap();
+                if decoder.feed(block_id, &block).unwrap() {
+                    break;
+                }
+            }
+
+            let ptr = buf.as_ptr();
+            decoder.recover_into_reusing(&mut buf).unwrap();
+            assert_eq!(buf, message);
+            assert_eq!(buf.as_ptr(), ptr);
+            assert_eq!(buf.capacity(), 4096);
+
+            decoder.reset().unwrap();
+        }
+    }
+
+    #[test]
+    fn results_use_the_crate_alias() {
+        let init = wirehair_init().unwrap();
+
+        let message: Vec<u8> = (0..520).map(|i| (i * 13) as u8).collect();
+        let encoder: Result<WirehairEncoder> = WirehairEncoder::from_slice(&message, 50);
+        let blocks = encoder.unwrap().encode_all(20).unwrap();
+        let decoder = WirehairDecoder::new(&init, 520, 50).unwrap();
+
+        let mut recovered = vec![0u8; 520];
+        let early: Result<()> = decoder.recover(&mut recovered[..10]);
+        assert_eq!(early, Err(WirehairError::NeedMore));
+
+        let solved: Result<bool> =
+            decoder.decode_many(blocks.iter().map(|(block_id, block)| (*block_id, &block[..])));
+        assert_eq!(solved, Ok(true));
+
+        let recovered_into: Result<()> = decoder.recover(&mut recovered);
+        assert_eq!(recovered_into, Ok(()));
+        assert_eq!(recovered, message);
+    }
+
+    #[test]
+    fn duplicates_are_not_decoded_twice() {
+        let init = wirehair_init().unwrap();
+
+        let message: Vec<u8> = (0..1000).map(|i| (i * 19 + 2) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+        let decoder = WirehairDecoder::new(&init, 1000, 50).unwrap();
+        let mut blocks = encoder.encode_all(30).unwrap();
+
+        let (block_id, block) = &blocks[7];
+        assert_eq!(decoder.decode_dedup(*block_id, block), Ok(false));
+        assert_eq!(decoder.decode_dedup(*block_id, block), Ok(false));
+        assert_eq!(decoder.blocks_received(), 1);
+
+        // Reordered, with every block sent twice
+        blocks.reverse();
+        let mut solved = false;
+        for (block_id, block) in blocks.iter().flat_map(|block| vec![block, block]) {
+            solved = decoder.decode_dedup(*block_id, block).unwrap();
+            if solved {
+                break;
+            }
+        }
+
+        assert!(solved);
+        assert!(decoder.blocks_received() <= 22, "{}", decoder.blocks_received());
+        assert_eq!(decoder.recover_vec(), Ok(message));
+    }
+
+    #[test]
+    fn message_size_limits_are_exact() {
+        assert!(wirehair_init().is_ok());
+
+        const LARGEST: u64 = max_message_size(3);
+        assert_eq!(LARGEST, 192_000);
+        assert_eq!(min_message_size(50), 51);
+
+        let message = vec![1u8; LARGEST as usize + 1];
+        let largest = FecParams::new(LARGEST, 3);
+        assert!(WirehairEncoder::with_params(&message, largest).is_ok());
+        assert_eq!(
+            WirehairEncoder::with_params(&message, FecParams::new(LARGEST + 1, 3)).err(),
+            Some(WirehairError::BadInputLargeN)
+        );
+
+        assert!(WirehairDecoder::with_params(FecParams::new(51, 50)).is_ok());
+        assert_eq!(
+            WirehairDecoder::with_params(FecParams::new(50, 50)).err(),
+            Some(WirehairError::BadInputSmallN)
+        );
+    }
+
+    #[test]
+    fn block_count_cap_matches_the_library() {
+        let init = wirehair_init().unwrap();
+
+        // The const has to be kept in sync by hand, for `max_message_size` and friends
+        assert_eq!(max_block_count(), MAX_BLOCK_COUNT);
+        assert!(max_block_count() >= 64000);
+
+        let largest = u64::from(max_block_count());
+        let message = vec![0u8; largest as usize + 1];
+        assert!(WirehairEncoder::new(&init, &message, largest, 1).is_ok());
+        // The library itself refuses one block more, without the Rust side checking anything first
+        assert!(WirehairEncoder::new(&init, &message, largest + 1, 1).is_err());
+        assert_eq!(
+            FecParams::new(largest + 1, 1).validate(),
+            Err(WirehairError::BadInputLargeN)
+        );
+    }
+
+    #[test]
+    fn batches_fill_every_buffer() {
+        assert!(wirehair_init().is_ok());
+
+        let message: Vec<u8> = (0..380).map(|i| (i * 23 + 1) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+
+        let mut storage = vec![[0u8; 64]; 8];
+        let mut bufs: Vec<&mut [u8]> = storage.iter_mut().map(|buf| &mut buf[..]).collect();
+        let lens = encoder.encode_batch(0, &mut bufs).unwrap();
+
+        // Blocks 0..7 are the message itself, 7 is the 30 byte tail, 8 is a repair block
+        assert_eq!(lens, vec![50, 50, 50, 50, 50, 50, 50, 30]);
+        for (block_id, buf) in bufs.iter().enumerate().take(7) {
+            assert_eq!(&buf[..50], &message[block_id * 50..block_id * 50 + 50]);
+        }
+        assert_eq!(&bufs[7][..30], &message[350..]);
+
+        let mut short = [0u8; 49];
+        let mut bufs: Vec<&mut [u8]> = vec![&mut storage[0], &mut short];
+        assert_eq!(encoder.encode_batch(8, &mut bufs), Err(WirehairError::InvalidInput));
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn recovery_is_logged() {
+        use std::sync::Mutex;
+
+        static RECORDS: Mutex<Vec<(log::Level, String)>> = Mutex::new(Vec::new());
+
+        struct Capture;
+
+        impl log::Log for Capture {
+            fn enabled(&self, _: &log::Metadata) -> bool {
+                true
+            }
+
+            fn log(&self, record: &log::Record) {
+                RECORDS.lock().unwrap().push((record.level(), record.args().to_string()));
+            }
+
+            fn flush(&self) {}
+        }
+
+        assert!(wirehair_init().is_ok());
+        log::set_logger(&Capture).unwrap();
+        log::set_max_level(log::LevelFilter::Debug);
+
+        let message: Vec<u8> = (0..500).map(|i| (i * 3) as u8).collect();
+        let blocks = WirehairEncoder::from_slice(&message, 50).unwrap().encode_all(10).unwrap();
+        assert_eq!(WirehairDecoder::recover_from(500, 50, blocks), Ok(message));
+
+        // Other tests run concurrently and log too, so only this test's message size is looked for
+        let records = RECORDS.lock().unwrap();
+        assert!(records.iter().any(|(level, text)| {
+            *level == log::Level::Info && text.starts_with("recovered 500 bytes from 10 blocks")
+        }));
+    }
+
+    #[test]
+    fn padded_len_covers_the_last_block() {
+        assert!(wirehair_init().is_ok());
+
+        let encoder = WirehairEncoder::from_slice(&[7u8; 523], 50).unwrap();
+        assert_eq!(encoder.message_len(), 523);
+        assert_eq!(encoder.padded_len(), 550);
+        assert_eq!(encoder.padded_len() - encoder.message_len(), 50 - 523 % 50);
+
+        let encoder = WirehairEncoder::from_slice(&[7u8; 500], 50).unwrap();
+        assert_eq!(encoder.padded_len(), encoder.message_len());
+    }
+
+    #[test]
+    fn init_token_is_threaded_through() -> Result<()> {
+        let init = wirehair_init()?;
+
+        let message: Vec<u8> = (0..500).map(|i| (i * 13) as u8).collect();
+        let encoder = WirehairEncoder::new(&init, &message, 500, 50)?;
+        let decoder = WirehairDecoder::new(&init, 500, 50)?;
+        let blocks = encoder.encode_all(10)?;
+        assert!(decoder.decode_many(blocks.iter().map(|(block_id, block)| (*block_id, &block[..])))?);
+        assert_eq!(decoder.recover_vec()?, message);
+
+        // Every call hands out an equal token
+        assert_eq!(wirehair_init(), Ok(init));
+
+        Ok(())
+    }
+
+    #[test]
+    fn block_ids_sort_like_plain_ids() {
+        let mut blocks = std::collections::BTreeMap::new();
+        for &block_id in [12u64, 3, 40, 0, 7].iter() {
+            blocks.insert(BlockId(block_id), vec![block_id as u8]);
+        }
+
+        let ids: Vec<BlockId> = blocks.keys().copied().collect();
+        assert_eq!(ids, vec![BlockId(0), BlockId(3), BlockId(7), BlockId(12), BlockId(40)]);
+        assert_eq!(blocks[&BlockId(7)], vec![7]);
+        assert!(BlockId(2) < BlockId(10));
+    }
+
+    #[test]
+    fn blocks_stream_through_io() {
+        assert!(wirehair_init().is_ok());
+
+        let message: Vec<u8> = (0..520).map(|i| (i * 19) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+
+        // Every third block is missing from the stream
+        let mut stream = Vec::new();
+        for block_id in (0..30).filter(|block_id| block_id % 3 != 2) {
+            encoder.encode_to_writer(block_id..block_id + 1, &mut stream).unwrap();
+        }
+        // Block 10 is the 20 byte tail
+        assert_eq!(&stream[12 * 7 + 50 * 7..][..12], &[10, 0, 0, 0, 0, 0, 0, 0, 20, 0, 0, 0]);
+
+        let decoder = WirehairDecoder::with_params(FecParams::new(520, 50)).unwrap();
+        let mut reader = &stream[..];
+        assert!(decoder.decode_from_reader(&mut reader).unwrap());
+        assert!(!reader.is_empty());
+        assert_eq!(decoder.recover_vec().unwrap(), message);
+
+        // A stream which ends early, one cut inside a frame and one with an oversized length
+        let decoder = WirehairDecoder::with_params(FecParams::new(520, 50)).unwrap();
+        assert!(!decoder.decode_from_reader(&mut &stream[..62 * 3]).unwrap());
+        let error = decoder.decode_from_reader(&mut &stream[62 * 3..62 * 4 + 5]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+
+        let mut oversized = stream[..12].to_vec();
+        oversized[8] = 51;
+        let error = decoder.decode_from_reader(&mut &oversized[..]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn original_blocks_are_message_slices() {
+        assert!(wirehair_init().is_ok());
+
+        let message: Vec<u8> = (0..520).map(|i| (i * 29 + 3) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+
+        assert_eq!(encoder.original_block(0).unwrap(), &message[..50]);
+        assert_eq!(encoder.original_block(4).unwrap(), &message[200..250]);
+        assert_eq!(encoder.original_block(10).unwrap(), &message[500..]);
+        assert_eq!(encoder.original_block(11), Err(WirehairError::InvalidInput));
+    }
+
+    #[test]
+    fn missing_originals_are_listed() {
+        let init = wirehair_init().unwrap();
+
+        let message: Vec<u8> = (0..250).map(|i| (i * 17) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+        let mut decoder = WirehairDecoder::new(&init, 250, 50).unwrap();
+        assert_eq!(decoder.missing_original_ids(), vec![0, 1, 2, 3, 4]);
+
+        // Repair block 7 doesn't fill any gap
+        for &block_id in [0u64, 1, 3, 7].iter() {
+            let mut block = [0u8; 50];
+            let len = encoder.encode_into(block_id, &mut block).unwrap();
+            assert!(!decoder.feed(block_id, &block[..len]).unwrap());
+        }
+        assert_eq!(decoder.missing_original_ids(), vec![2, 4]);
+
+        decoder.reset().unwrap();
+        assert_eq!(decoder.missing_original_ids(), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn result_helpers_cover_both_variants() {
+        assert!(WirehairResult::Success.is_complete());
+        assert!(!WirehairResult::Success.is_need_more());
+        assert_eq!(WirehairResult::Success.into_option(), Some(()));
+
+        assert!(!WirehairResult::NeedMore.is_complete());
+        assert!(WirehairResult::NeedMore.is_need_more());
+        assert_eq!(WirehairResult::NeedMore.into_option(), None);
+    }
+
+    #[test]
+    fn short_encode_buffers_are_rejected() {
+        assert!(wirehair_init().is_ok());
+
+        let message: Vec<u8> = (0..520).map(|i| (i * 3) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+
+        let mut block = [0u8; 50];
+        assert_eq!(encoder.encode(0, &mut block[..10]), Err(WirehairError::InvalidInput));
+        assert_eq!(encoder.encode(30, &mut block[..10]), Err(WirehairError::InvalidInput));
+        // Even the 20 byte last block needs a whole block of room
+        assert_eq!(encoder.encode(10, &mut block[..20]), Err(WirehairError::InvalidInput));
+        assert_eq!(block, [0u8; 50]);
+
+        assert_eq!(encoder.encode(10, &mut block), Ok(20));
+    }
+
+    #[test]
+    fn block_ids_beyond_u32_are_rejected() {
+        let init = wirehair_init().unwrap();
+        let message: Vec<u8> = (0..520).map(|i| (i * 3) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+        let decoder = WirehairDecoder::new(&init, 520, 50).unwrap();
+
+        // Truncated to 32 bits these would be block 0
+        let mut block = [0u8; 50];
+        assert_eq!(encoder.encode(1u64 << 32, &mut block), Err(WirehairError::InvalidInput));
+        assert_eq!(block, [0u8; 50]);
+        encoder.encode(0, &mut block).unwrap();
+        assert_eq!(decoder.decode(1u64 << 32, &block, 50), Err(WirehairError::InvalidInput));
+        assert_eq!(decoder.blocks_received(), 0);
+
+        assert!(encoder.encode(u64::from(u32::MAX), &mut block).is_ok());
+    }
+
+    #[test]
+    fn shared_encoder_serves_many_threads() {
+        assert!(wirehair_init().is_ok());
+
+        let message: Vec<u8> = (0..520).map(|i| (i * 31 + 7) as u8).collect();
+        let reference = WirehairEncoder::from_slice(&message, 50).unwrap();
+        let shared = SharedEncoder::new(WirehairEncoder::from_slice(&message, 50).unwrap());
+
+        // Each thread encodes its own ids, original and repair ones
+        let handles: Vec<_> = (0..4u64)
+            .map(|thread| {
+                let shared = shared.clone();
+                std::thread::spawn(move || {
+                    (0..10)
+                        .map(|i| {
+                            let block_id = thread * 10 + i;
+                            let mut block = vec![0u8; 50];
+                            let len = shared.encode(block_id, &mut block).unwrap();
+                            block.truncate(len);
+                            (block_id, block)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            for (block_id, block) in handle.join().unwrap() {
+                let mut expected = vec![0u8; 50];
+                let len = reference.encode_into(block_id, &mut expected).unwrap();
+                assert_eq!(block, &expected[..len]);
+            }
+        }
+    }
+
+    #[test]
+    fn recover_len_excludes_padding() {
+        let init = wirehair_init().unwrap();
+
+        let message: Vec<u8> = (0..523).map(|i| (i * 13 + 2) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+        let decoder = WirehairDecoder::new(&init, 523, 50).unwrap();
+        let blocks = encoder.encode_all(11).unwrap();
+
+        // Room for all 11 blocks, of which the last one has 23 bytes
+        let mut out = vec![0xAAu8; 550];
+        let early = blocks[..10].iter().map(|(block_id, block)| (*block_id, &block[..]));
+        assert!(!decoder.decode_many(early).unwrap());
+        assert_eq!(decoder.recover_len(&mut out), Err(WirehairError::NeedMore));
+        assert!(out.iter().all(|&byte| byte == 0xAA));
+
+        assert!(decoder.feed(blocks[10].0, &blocks[10].1).unwrap());
+        let len = decoder.recover_len(&mut out).unwrap();
+        assert_eq!(len, 523);
+        assert_eq!(&out[..len], &message[..]);
+        assert!(out[len..].iter().all(|&byte| byte == 0xAA));
+
+        assert_eq!(decoder.recover_len(&mut out[..522]), Err(WirehairError::InvalidInput));
+    }
+
+    #[test]
+    fn fixed_encoder_returns_arrays() {
+        let init = wirehair_init().unwrap();
+
+        let message: Vec<u8> = (0..520).map(|i| (i * 7 + 5) as u8).collect();
+        let encoder = FixedEncoder::<50>::from_slice(&message).unwrap();
+        let decoder = WirehairDecoder::new(&init, 520, 50).unwrap();
+
+        let (block, len) = encoder.encode(0).unwrap();
+        assert_eq!((&block[..], len), (&message[..50], 50));
+        let (block, len) = encoder.encode(10).unwrap();
+        assert_eq!(&block[..len], &message[500..]);
+
+        let mut solved = false;
+        for block_id in (0..30u64).filter(|block_id| block_id % 4 != 0) {
+            let (block, len) = encoder.encode(block_id).unwrap();
+            if decoder.feed(block_id, &block[..len]).unwrap() {
+                solved = true;
+                break;
+            }
+        }
+        assert!(solved);
+        assert_eq!(decoder.recover_vec().unwrap(), message);
+
+        let other = WirehairEncoder::from_slice(&message, 64).unwrap();
+        assert_eq!(FixedEncoder::<50>::new(other).err(), Some(WirehairError::InvalidInput));
+    }
+
+    #[test]
+    fn premature_finalize_is_an_error() {
+        let init = wirehair_init().unwrap();
+
+        let message: Vec<u8> = (0..500).map(|i| (i * 3 + 1) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+        let decoder = WirehairDecoder::new(&init, 500, 50).unwrap();
+
+        let mut out = [0xAAu8; 500];
+        let blocks = encoder.encode_all(10).unwrap();
+        for (block_id, block) in &blocks[..9] {
+            assert!(!decoder.feed(*block_id, block).unwrap());
+        }
+        assert_eq!(decoder.finalize(&mut out), Err(WirehairError::NeedMore));
+        assert!(out.iter().all(|&byte| byte == 0xAA));
+
+        assert!(decoder.feed(blocks[9].0, &blocks[9].1).unwrap());
+        assert_eq!(decoder.finalize(&mut out), Ok(()));
+        assert_eq!(&out[..], &message[..]);
+    }
+
+    #[test]
+    fn premature_recover_is_an_error() {
+        let init = wirehair_init().unwrap();
+
+        let message: Vec<u8> = (0..500).map(|i| (i * 7 + 3) as u8).collect();
+        let encoder = WirehairEncoder::from_slice(&message, 50).unwrap();
+        let decoder = WirehairDecoder::new(&init, 500, 50).unwrap();
+
+        let mut out = [0xAAu8; 500];
+        let blocks = encoder.encode_all(10).unwrap();
+        for (block_id, block) in &blocks[..9] {
+            assert!(!decoder.feed(*block_id, block).unwrap());
+        }
+        assert_eq!(decoder.recover(&mut out), Err(WirehairError::NeedMore));
+        assert!(out.iter().all(|&byte| byte == 0xAA));
+        assert_eq!(decoder.stats().snapshot().recoveries, 0);
 
-        assert!(wirehair_decoder_to_encoder(decoder).is_ok());
+        assert!(decoder.feed(blocks[9].0, &blocks[9].1).unwrap());
+        assert_eq!(decoder.recover(&mut out), Ok(()));
+        assert_eq!(&out[..], &message[..]);
+        assert_eq!(decoder.stats().snapshot().recoveries, 1);
     }
 }
@@ -0,0 +1,172 @@
+use std::ops::Deref;
+
+use crate::wirehair::{wirehair_init, FecParams, Result, WirehairDecoder, WirehairEncoder};
+
+/// A message zero padded to a whole number of blocks, so every block including the last one
+/// is `block_size` bytes long. Remembers the real length, which the receiver needs to trim
+/// the recovered message back to it.
+pub struct PaddedMessage {
+    padded: Vec<u8>,
+    message_len: u64,
+    block_size: u32,
+}
+
+impl PaddedMessage {
+    pub fn new(message: &[u8], block_size_bytes: u32) -> Result<PaddedMessage> {
+        let message_len = message.len() as u64;
+        let block_count = FecParams::new(message_len, block_size_bytes).validate()?;
+
+        // The padding is always zeros, so the same message encodes to the same blocks
+        let mut padded = message.to_vec();
+        padded.resize(block_count as usize * block_size_bytes as usize, 0);
+
+        Ok(PaddedMessage {
+            padded,
+            message_len,
+            block_size: block_size_bytes,
+        })
+    }
+
+    pub fn message_len(&self) -> u64 {
+        self.message_len
+    }
+
+    pub fn padded_len(&self) -> u64 {
+        self.padded.len() as u64
+    }
+
+    /// The message followed by the zero padding
+    pub fn as_padded(&self) -> &[u8] {
+        &self.padded
+    }
+
+    /// An encoder over the padded message
+    pub fn encoder(&self) -> Result<WirehairEncoder> {
+        WirehairEncoder::from_slice(&self.padded, self.block_size)
+    }
+
+    /// A decoder for blocks of a `PaddedMessage` of `message_len` real bytes
+    pub fn decoder(message_len: u64, block_size_bytes: u32) -> Result<PaddedDecoder> {
+        let block_count = FecParams::new(message_len, block_size_bytes).validate()?;
+        let padded_len = u64::from(block_count) * u64::from(block_size_bytes);
+
+        Ok(PaddedDecoder {
+            decoder: WirehairDecoder::new(&wirehair_init()?, padded_len, block_size_bytes)?,
+            message_len,
+        })
+    }
+}
+
+/// Decodes a padded message and trims the padding off on recovery.
+/// Derefs to `WirehairDecoder` for decoding.
+pub struct PaddedDecoder {
+    decoder: WirehairDecoder,
+    message_len: u64,
+}
+
+impl PaddedDecoder {
+    pub fn message_len(&self) -> u64 {
+        self.message_len
+    }
+
+    /// Same as `WirehairDecoder::recover_vec`, but returns only the `message_len` real bytes
+    pub fn recover_vec(&self) -> Result<Vec<u8>> {
+        let mut message = self.decoder.recover_vec()?;
+        message.truncate(self.message_len as usize);
+
+        Ok(message)
+    }
+
+    /// Same as `WirehairDecoder::recover_len`, but only counts the `message_len` real bytes.
+    /// `out` still has to hold the padded message.
+    pub fn recover_len(&self, out: &mut [u8]) -> Result<usize> {
+        self.decoder.recover_len(out)?;
+
+        Ok(self.message_len as usize)
+    }
+
+    pub fn into_decoder(self) -> WirehairDecoder {
+        self.decoder
+    }
+}
+
+impl Deref for PaddedDecoder {
+    type Target = WirehairDecoder;
+
+    fn deref(&self) -> &WirehairDecoder {
+        &self.decoder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wirehair::{wirehair_init, WirehairError};
+
+    #[test]
+    fn padding_is_trimmed_on_recovery() {
+        assert!(wirehair_init().is_ok());
+
+        let message: Vec<u8> = (0..503u32).map(|i| (i * 41 + 3) as u8).collect();
+        let padded = PaddedMessage::new(&message, 50).unwrap();
+        assert_eq!(padded.message_len(), 503);
+        assert_eq!(padded.padded_len(), 550);
+        assert!(padded.as_padded()[503..].iter().all(|&byte| byte == 0));
+
+        let encoder = padded.encoder().unwrap();
+        let decoder = PaddedMessage::decoder(503, 50).unwrap();
+        for block in encoder.blocks(0).take(20) {
+            let (block_id, block) = block.unwrap();
+            // The last block is as long as the rest
+            assert_eq!(block.len(), 50);
+
+            // lose one block, so the padded last block takes part in solving
+            if block_id.0 == 4 {
+                continue;
+            }
+
+            if decoder.feed(block_id, &block).unwrap() {
+                break;
+            }
+        }
+
+        let recovered = decoder.recover_vec().unwrap();
+        assert_eq!(recovered.len(), 503);
+        assert_eq!(recovered, message);
+    }
+
+    #[test]
+    fn recover_len_counts_real_bytes() {
+        assert!(wirehair_init().is_ok());
+
+        let message = [9u8; 503];
+        let padded = PaddedMessage::new(&message, 50).unwrap();
+        let encoder = padded.encoder().unwrap();
+        let decoder = PaddedMessage::decoder(503, 50).unwrap();
+        let blocks = encoder.encode_all(11).unwrap();
+        let blocks = blocks.iter().map(|(block_id, block)| (*block_id, &block[..]));
+        assert!(decoder.decode_many(blocks).unwrap());
+
+        let mut out = [0u8; 550];
+        assert_eq!(decoder.recover_len(&mut out), Ok(503));
+        assert_eq!(&out[..503], &message[..]);
+    }
+
+    #[test]
+    fn recover_len_waits_for_the_whole_message() {
+        assert!(wirehair_init().is_ok());
+
+        let message = [9u8; 503];
+        let padded = PaddedMessage::new(&message, 50).unwrap();
+        let encoder = padded.encoder().unwrap();
+        let decoder = PaddedMessage::decoder(503, 50).unwrap();
+        let blocks = encoder.encode_all(10).unwrap();
+        let blocks = blocks.iter().map(|(block_id, block)| (*block_id, &block[..]));
+        assert!(!decoder.decode_many(blocks).unwrap());
+
+        let mut out = [0u8; 550];
+        assert_eq!(decoder.recover_len(&mut out), Err(WirehairError::NeedMore));
+        assert_eq!(decoder.recover_vec(), Err(WirehairError::NeedMore));
+        assert!(out.iter().all(|&byte| byte == 0));
+    }
+}
@@ -0,0 +1,203 @@
+use std::ops::Range;
+
+use crate::wirehair::{
+    wirehair_init, BlockId, FecParams, Result, WirehairDecoder, WirehairEncoder, WirehairError,
+    WirehairResult,
+};
+
+// Bytes of the message carried by original block `index`, the last one may be shorter
+fn original_range(index: usize, block_size: usize, message_size: usize) -> Range<usize> {
+    let start = index * block_size;
+
+    start..message_size.min(start + block_size)
+}
+
+/// Sends blocks `0..N` as plain slices of the message, so a receiver which loses nothing can
+/// reassemble it without running the codec. Only repair blocks (`block_id >= N`) are encoded.
+pub struct SystematicEncoder {
+    encoder: WirehairEncoder,
+}
+
+impl SystematicEncoder {
+    pub fn new(encoder: WirehairEncoder) -> SystematicEncoder {
+        SystematicEncoder { encoder }
+    }
+
+    pub fn encoder(&self) -> &WirehairEncoder {
+        &self.encoder
+    }
+
+    /// Writes block `block_id` into `block` and returns its valid length.
+    /// The last original block is zero padded up to `block_size` as far as `block` allows.
+    pub fn encode(&self, block_id: impl Into<BlockId>, block: &mut [u8]) -> Result<u32> {
+        let block_id = block_id.into();
+        if block_id.0 >= u64::from(self.encoder.block_count()) {
+            return self.encoder.encode(block_id, block);
+        }
+
+        let block_size = self.encoder.block_size() as usize;
+        let original = &self.encoder.message()
+            [original_range(block_id.0 as usize, block_size, self.encoder.message().len())];
+        if block.len() < original.len() {
+            return Err(WirehairError::InvalidInput);
+        }
+
+        let padded = block.len().min(block_size);
+        block[..original.len()].copy_from_slice(original);
+        block[original.len()..padded].fill(0);
+
+        Ok(original.len() as u32)
+    }
+}
+
+/// Counterpart of `SystematicEncoder`. Original blocks are collected as they are; the codec only
+/// gets involved once a repair block arrives, and then it is fed the originals received so far first.
+pub struct SystematicDecoder {
+    decoder: WirehairDecoder,
+    message: Vec<u8>,
+    received: Vec<bool>,
+    missing: u32,
+    repairing: bool,
+}
+
+impl SystematicDecoder {
+    pub fn new(message_size_bytes: u64, block_size_bytes: u32) -> Result<SystematicDecoder> {
+        let block_count = FecParams::new(message_size_bytes, block_size_bytes).validate()?;
+        let decoder = WirehairDecoder::new(&wirehair_init()?, message_size_bytes, block_size_bytes)?;
+
+        Ok(SystematicDecoder {
+            decoder,
+            message: vec![0u8; message_size_bytes as usize],
+            received: vec![false; block_count as usize],
+            missing: block_count,
+            repairing: false,
+        })
+    }
+
+    pub fn decoder(&self) -> &WirehairDecoder {
+        &self.decoder
+    }
+
+    /// Takes block `block_id` of the valid length produced by `SystematicEncoder::encode`
+    pub fn decode(
+        &mut self,
+        block_id: impl Into<BlockId>,
+        block: &[u8],
+    ) -> Result<WirehairResult> {
+        let block_id = block_id.into();
+        if self.missing == 0 {
+            return Ok(WirehairResult::Success);
+        }
+
+        if block_id.0 < self.received.len() as u64 {
+            let index = block_id.0 as usize;
+            let range = original_range(index, self.block_size(), self.message.len());
+            if block.len() != range.len() {
+                return Err(WirehairError::InvalidInput);
+            }
+
+            if !self.received[index] {
+                self.message[range].copy_from_slice(block);
+                self.received[index] = true;
+                self.missing -= 1;
+            }
+            if self.missing == 0 {
+                return Ok(WirehairResult::Success);
+            }
+            if !self.repairing {
+                return Ok(WirehairResult::NeedMore);
+            }
+        } else if !self.repairing {
+            self.repairing = true;
+            if self.feed_originals()? {
+                return Ok(WirehairResult::Success);
+            }
+        }
+
+        self.decoder.decode(block_id, block, block.len() as u32)
+    }
+
+    /// Returns the message, straight from the original blocks when all of them arrived
+    pub fn recover_vec(&self) -> Result<Vec<u8>> {
+        if self.missing == 0 {
+            return Ok(self.message.clone());
+        }
+
+        self.decoder.recover_vec()
+    }
+
+    fn block_size(&self) -> usize {
+        self.decoder.block_size() as usize
+    }
+
+    fn feed_originals(&self) -> Result<bool> {
+        let block_size = self.block_size();
+        let originals = self
+            .received
+            .iter()
+            .enumerate()
+            .filter(|&(_, &received)| received)
+            .map(|(index, _)| {
+                let range = original_range(index, block_size, self.message.len());
+                (index as u64, &self.message[range])
+            });
+
+        self.decoder.decode_many(originals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wirehair::wirehair_init;
+
+    #[test]
+    fn originals_bypass_the_codec() {
+        assert!(wirehair_init().is_ok());
+
+        let message: Vec<u8> = (0..1010u32).map(|i| (i * 13 + i / 7) as u8).collect();
+        let encoder = SystematicEncoder::new(WirehairEncoder::from_slice(&message, 100).unwrap());
+        let mut block = vec![0xFFu8; 100];
+
+        assert_eq!(encoder.encode(0, &mut block), Ok(100));
+        assert_eq!(&block[..], &message[..100]);
+
+        // The last block carries 10 bytes and is padded with zeros
+        assert_eq!(encoder.encode(10, &mut block), Ok(10));
+        assert_eq!(&block[..10], &message[1000..]);
+        assert!(block[10..].iter().all(|&byte| byte == 0));
+
+        let mut decoder = SystematicDecoder::new(1010, 100).unwrap();
+        let mut result = Ok(WirehairResult::NeedMore);
+        for block_id in 0..11u64 {
+            let len = encoder.encode(block_id, &mut block).unwrap() as usize;
+            result = decoder.decode(block_id, &block[..len]);
+        }
+
+        assert_eq!(result, Ok(WirehairResult::Success));
+        assert_eq!(decoder.decoder().blocks_received(), 0);
+        assert_eq!(decoder.recover_vec(), Ok(message));
+    }
+
+    #[test]
+    fn lost_originals_are_repaired() {
+        assert!(wirehair_init().is_ok());
+
+        let message: Vec<u8> = (0..1010u32).map(|i| (i * 29 + i / 3) as u8).collect();
+        let encoder = SystematicEncoder::new(WirehairEncoder::from_slice(&message, 100).unwrap());
+        let mut decoder = SystematicDecoder::new(1010, 100).unwrap();
+        let mut block = vec![0u8; 100];
+
+        let mut result = Ok(WirehairResult::NeedMore);
+        for block_id in (0..40u64).filter(|block_id| block_id % 4 != 1) {
+            let len = encoder.encode(block_id, &mut block).unwrap() as usize;
+            result = decoder.decode(block_id, &block[..len]);
+            if result == Ok(WirehairResult::Success) {
+                break;
+            }
+        }
+
+        assert_eq!(result, Ok(WirehairResult::Success));
+        assert_eq!(decoder.recover_vec(), Ok(message));
+    }
+}
@@ -0,0 +1,45 @@
+use crate::wirehair::{wirehair_init, Result, WirehairDecoder, WirehairEncoder, WirehairError};
+
+/// The whole flow in one call: encodes `message`, loses every `drop_every`-th block
+/// (none for 0), decodes the rest and returns the recovered message.
+/// Fails with `ExtraInsufficient` if the loss is too heavy to recover from, e.g. for `drop_every = 1`.
+pub fn roundtrip_demo(
+    message: &[u8],
+    block_size: u32,
+    drop_every: u64,
+) -> Result<Vec<u8>> {
+    let init = wirehair_init()?;
+
+    let encoder = WirehairEncoder::from_slice(message, block_size)?;
+    let decoder = WirehairDecoder::new(&init, message.len() as u64, block_size)?;
+
+    // Losing every second block needs about 2N ids, anything worse is given up on
+    let max_ids = 2 * encoder.block_count() as usize + 64;
+    for block in encoder.blocks(0).take(max_ids) {
+        let (block_id, block) = block?;
+
+        if drop_every != 0 && (block_id.0 + 1) % drop_every == 0 {
+            continue;
+        }
+
+        if decoder.feed(block_id, &block)? {
+            return decoder.recover_vec();
+        }
+    }
+
+    Err(WirehairError::ExtraInsufficient)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_demo_works() {
+        let message: Vec<u8> = (0..500).map(|i| (i * 3) as u8).collect();
+
+        assert_eq!(roundtrip_demo(&message, 50, 3), Ok(message.clone()));
+        assert_eq!(roundtrip_demo(&message, 50, 0), Ok(message.clone()));
+        assert_eq!(roundtrip_demo(&message, 50, 1), Err(WirehairError::ExtraInsufficient));
+    }
+}
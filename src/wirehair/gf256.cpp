@@ -604,6 +604,22 @@ extern "C" int gf256_init_(int version) {
     return 0;
 }
 
+extern "C" int gf256_simd_level(void) {
+#if defined(GF256_TRY_NEON)
+    if (CpuHasNeon)
+        return 4;
+#elif !defined(GF256_TARGET_MOBILE)
+# ifdef GF256_TRY_AVX2
+    if (CpuHasAVX2)
+        return 3;
+# endif // GF256_TRY_AVX2
+    if (CpuHasSSSE3)
+        return 2;
+    return 1; // SSE2 is used unconditionally on x86
+#endif // GF256_TARGET_MOBILE
+    return 0;
+}
+
 
 //------------------------------------------------------------------------------
 // Operations
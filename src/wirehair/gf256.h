@@ -200,6 +200,12 @@ extern int gf256_init_(int version);
 
 #define gf256_init() gf256_init_(GF256_VERSION)
 
+/**
+    Returns the SIMD path picked by gf256_init():
+    0 = none, 1 = SSE2, 2 = SSSE3, 3 = AVX2, 4 = NEON
+*/
+extern int gf256_simd_level(void);
+
 
 //------------------------------------------------------------------------------
 // Math Operations
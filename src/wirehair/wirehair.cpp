@@ -247,5 +247,25 @@ WIREHAIR_EXPORT void wirehair_free(
     delete object;
 }
 
+WIREHAIR_EXPORT int wirehair_simd_level() {
+    return gf256_simd_level();
+}
+
+WIREHAIR_EXPORT unsigned wirehair_max_block_count() {
+    return CAT_WIREHAIR_MAX_N;
+}
+
+WIREHAIR_EXPORT uint8_t wirehair_gf256_mul(uint8_t x, uint8_t y) {
+    return gf256_mul(x, y);
+}
+
+WIREHAIR_EXPORT uint8_t wirehair_gf256_div(uint8_t x, uint8_t y) {
+    return gf256_div(x, y);
+}
+
+WIREHAIR_EXPORT uint8_t wirehair_gf256_inv(uint8_t x) {
+    return gf256_inv(x);
+}
+
 
 } // extern "C"
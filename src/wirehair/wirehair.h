@@ -315,6 +315,32 @@ WIREHAIR_EXPORT void wirehair_free(
         WirehairCodec codec ///< Codec object to free
 );
 
+/**
+    wirehair_simd_level()
+
+    Returns the SIMD path the math library picked at wirehair_init():
+    0 = none, 1 = SSE2, 2 = SSSE3, 3 = AVX2, 4 = NEON
+*/
+WIREHAIR_EXPORT int wirehair_simd_level();
+
+/**
+    wirehair_max_block_count()
+
+    Returns the largest N the codec accepts (CAT_WIREHAIR_MAX_N)
+*/
+WIREHAIR_EXPORT unsigned wirehair_max_block_count();
+
+/**
+    wirehair_gf256_mul(), wirehair_gf256_div(), wirehair_gf256_inv()
+
+    The GF(256) math of the codec, with the polynomial it uses (0x14D):
+    x * y, x / y and 1 / x.  Requires wirehair_init().  Division by zero and
+    the inverse of zero return 0.
+*/
+WIREHAIR_EXPORT uint8_t wirehair_gf256_mul(uint8_t x, uint8_t y);
+WIREHAIR_EXPORT uint8_t wirehair_gf256_div(uint8_t x, uint8_t y);
+WIREHAIR_EXPORT uint8_t wirehair_gf256_inv(uint8_t x);
+
 
 #ifdef __cplusplus
 }
@@ -0,0 +1,56 @@
+// The counting allocator replaces the global one for this whole test binary,
+// which is why this test lives apart from the unit tests
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use wirehair_wrapper::wirehair::{wirehair_init, WirehairDecoder, WirehairEncoder};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+#[test]
+fn streaming_blocks_does_not_allocate() {
+    let init = wirehair_init().unwrap();
+
+    let message: Vec<u8> = (0..10_000).map(|i| (i * 7) as u8).collect();
+    let encoder = WirehairEncoder::from_slice(&message, 100).unwrap();
+    let decoder = WirehairDecoder::new(&init, 10_000, 100).unwrap();
+
+    let mut buffer = [0u8; 100];
+    let mut blocks = encoder.blocks_into(&mut buffer, 0);
+
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    let mut solved = false;
+    for _ in 0..1000 {
+        let (block_id, block) = blocks.next_block().unwrap();
+
+        // drop every third block, so the decoder has some work to do
+        if block_id.0 % 3 == 0 {
+            continue;
+        }
+        if decoder.feed(block_id, block).unwrap() {
+            solved = true;
+            break;
+        }
+    }
+    let after = ALLOCATIONS.load(Ordering::SeqCst);
+
+    assert!(solved);
+    assert_eq!(after - before, 0);
+    assert_eq!(decoder.recover_vec(), Ok(message));
+}
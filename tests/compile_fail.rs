@@ -0,0 +1,6 @@
+// Misuse which the type system is supposed to rule out, checked against the compiler's errors
+#[test]
+fn misuse_does_not_compile() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
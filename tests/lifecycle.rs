@@ -0,0 +1,158 @@
+// Ownership of the native codecs across creation, conversion and drop. Every path here frees
+// each handle exactly once, which plain `cargo test` can't tell apart from a double free or a
+// leak. Run under AddressSanitizer (leak checking included) to have it checked:
+//
+//   RUSTFLAGS=-Zsanitizer=address cargo +nightly test --test lifecycle \
+//       --target x86_64-unknown-linux-gnu
+//
+// Miri can't execute the C++ codec, so these are the memory-safety tests of the FFI layer.
+#![allow(deprecated)]
+
+use std::convert::TryFrom;
+
+use wirehair_wrapper::wirehair::{
+    wirehair_decoder_to_encoder, wirehair_init, Codec, CodecPool, WirehairDecoder, WirehairEncoder,
+    WirehairError,
+};
+
+const MESSAGE_SIZE: usize = 5000;
+const BLOCK_SIZE: u32 = 100;
+
+fn message() -> Vec<u8> {
+    (0..MESSAGE_SIZE).map(|i| (i * 17 + i / 100) as u8).collect()
+}
+
+fn solved_decoder(encoder: &WirehairEncoder) -> WirehairDecoder {
+    let init = wirehair_init().unwrap();
+    let decoder = WirehairDecoder::new(&init, MESSAGE_SIZE as u64, BLOCK_SIZE).unwrap();
+
+    let mut block = [0u8; BLOCK_SIZE as usize];
+    for block_id in (0..200u64).filter(|block_id| block_id % 4 != 1) {
+        let len = encoder.encode_into(block_id, &mut block).unwrap();
+        if decoder.feed(block_id, &block[..len]).unwrap() {
+            return decoder;
+        }
+    }
+
+    panic!("the decoder wasn't solved by 150 blocks");
+}
+
+#[test]
+fn encoders_free_their_codec_on_drop() {
+    let message = message();
+    let encoder = WirehairEncoder::from_slice(&message, BLOCK_SIZE).unwrap();
+    // The encoder reads its own copy of the message, not the caller's buffer
+    drop(message);
+
+    let clone = encoder.clone();
+    let mut block = [0u8; BLOCK_SIZE as usize];
+    encoder.encode_into(70, &mut block).unwrap();
+    drop(encoder);
+
+    // The clone has a codec of its own, which outlives the original
+    clone.encode_into(70, &mut block).unwrap();
+}
+
+#[test]
+fn converted_decoders_hand_their_codec_over() {
+    let message = message();
+    let encoder = WirehairEncoder::from_slice(&message, BLOCK_SIZE).unwrap();
+
+    let mut decoder = solved_decoder(&encoder);
+    decoder.retain_original_blocks();
+    let relay = WirehairEncoder::try_from(decoder).unwrap();
+    assert_eq!(relay.message_size(), MESSAGE_SIZE as u64);
+
+    // The deprecated function goes through the same conversion
+    let relay_too = wirehair_decoder_to_encoder(solved_decoder(&encoder)).unwrap();
+
+    let mut block = [0u8; BLOCK_SIZE as usize];
+    let mut expected = [0u8; BLOCK_SIZE as usize];
+    for block_id in [0u64, 49, 50, 120] {
+        encoder.encode_into(block_id, &mut expected).unwrap();
+        relay.encode_into(block_id, &mut block).unwrap();
+        assert_eq!(block, expected);
+        relay_too.encode_into(block_id, &mut block).unwrap();
+        assert_eq!(block, expected);
+    }
+
+    // Both the converted codec and the clone of it are freed by the encoders
+    let relay_clone = relay.clone();
+    drop(relay);
+    drop(relay_clone);
+    drop(relay_too);
+}
+
+#[test]
+fn refused_conversions_keep_the_decoder_freeing_its_codec() {
+    let init = wirehair_init().unwrap();
+    let decoder = WirehairDecoder::new(&init, MESSAGE_SIZE as u64, BLOCK_SIZE).unwrap();
+
+    // The decoder is consumed and dropped inside, the codec must be freed with it
+    assert_eq!(WirehairEncoder::try_from(decoder).err(), Some(WirehairError::NeedMore));
+
+    let decoder = WirehairDecoder::new(&init, MESSAGE_SIZE as u64, BLOCK_SIZE).unwrap();
+    let codec = Codec::Decoder(decoder);
+    assert_eq!(codec.into_encoder().err(), Some(WirehairError::NeedMore));
+}
+
+#[test]
+fn failed_creations_leave_nothing_to_free() {
+    let init = wirehair_init().unwrap();
+    // N = 64001 gets past the size checks of the wrapper, the library returns null for it
+    let too_large = vec![0u8; 64_001];
+
+    assert!(WirehairEncoder::new(&init, &too_large, 64_001, 1).is_err());
+    assert!(WirehairDecoder::new(&init, 64_001, 1).is_err());
+
+    // The library frees a codec handed over for reuse when creation fails
+    let message = message();
+    let old = WirehairEncoder::from_slice(&message, BLOCK_SIZE).unwrap();
+    assert!(WirehairEncoder::new_reusing(old, &too_large, 64_001, 1).is_err());
+    let old = WirehairDecoder::new(&init, MESSAGE_SIZE as u64, BLOCK_SIZE).unwrap();
+    assert!(WirehairDecoder::new_reusing(old, 64_001, 1).is_err());
+
+    // Same for an idle handle of a pool
+    let pool = CodecPool::new(1);
+    drop(pool.decoder(64_000, 1).unwrap());
+    assert_eq!(pool.idle(), 1);
+    assert!(pool.encoder(&too_large, 1).is_err());
+    assert_eq!(pool.idle(), 0);
+    drop(pool);
+}
+
+#[test]
+fn reset_decoders_reuse_their_codec() {
+    let message = message();
+    let encoder = WirehairEncoder::from_slice(&message, BLOCK_SIZE).unwrap();
+
+    let mut decoder = solved_decoder(&encoder);
+    for _ in 0..3 {
+        decoder.reset().unwrap();
+    }
+    drop(decoder);
+}
+
+#[test]
+fn pooled_codecs_are_freed_by_the_pool() {
+    let message = message();
+    let pool = CodecPool::new(1);
+
+    let first = pool.encoder(&message, BLOCK_SIZE).unwrap();
+    let second = pool.encoder(&message, BLOCK_SIZE).unwrap();
+    // One handle is kept, the other one is freed right away
+    drop(first);
+    drop(second);
+    assert_eq!(pool.idle(), 1);
+
+    // The kept handle moves into a decoder and back
+    let decoder = pool.decoder(MESSAGE_SIZE as u64, BLOCK_SIZE).unwrap();
+    assert_eq!(pool.reused(), 1);
+    drop(decoder);
+
+    // The last clone of the pool frees what is still idle
+    let clone = pool.clone();
+    drop(pool);
+    assert_eq!(clone.idle(), 1);
+    drop(clone);
+}
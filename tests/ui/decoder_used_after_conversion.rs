@@ -0,0 +1,11 @@
+use std::convert::TryFrom;
+
+use wirehair_wrapper::wirehair::{wirehair_init, WirehairDecoder, WirehairEncoder};
+
+fn main() {
+    let init = wirehair_init().unwrap();
+    let decoder = WirehairDecoder::new(&init, 500, 50).unwrap();
+
+    let _encoder = WirehairEncoder::try_from(decoder);
+    let _ = decoder.recover_vec();
+}